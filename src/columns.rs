@@ -0,0 +1,245 @@
+extern crate chrono;
+
+use std::borrow::Cow;
+
+use crate::log_store::LogStoreLinear;
+use crate::model;
+use crate::model_internal::Timestamp;
+use crate::settings;
+
+// Column model of the custom-drawn log view. The message column is always
+// the last one and takes up the remaining space, all other columns have a
+// width that is either measured from the font or set by the user.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnKind {
+	Timestamp,
+	TimeDelta, //Time difference to the previous visible entry
+	Severity,
+	Source,
+	CustomField(String),
+	Message,
+}
+
+pub struct Column {
+	pub kind: ColumnKind,
+	pub visible: bool,
+	pub user_width: Option<f64>, //set by dragging the header separator
+	pub auto_width: f64,         //measured from the font, see `sample_text`
+}
+
+pub const COLUMN_PADDING: f64 = 10.0;
+pub const COLUMN_MIN_WIDTH: f64 = 20.0;
+
+impl Column {
+	pub fn new(kind: ColumnKind, visible: bool) -> Column {
+		Column {
+			kind,
+			visible,
+			user_width: None,
+			auto_width: 0.0,
+		}
+	}
+
+	pub fn title(&self) -> &str {
+		match &self.kind {
+			ColumnKind::Timestamp => "Timestamp",
+			ColumnKind::TimeDelta => "Δt",
+			ColumnKind::Severity => "Sev",
+			ColumnKind::Source => "Source",
+			ColumnKind::CustomField(name) => name,
+			ColumnKind::Message => "Message",
+		}
+	}
+
+	pub fn width(&self) -> f64 {
+		self.user_width.unwrap_or(self.auto_width)
+	}
+
+	//Representative text to measure the default width of the column
+	pub fn sample_text(&self) -> &str {
+		match &self.kind {
+			ColumnKind::Timestamp => "00.00.00 00:00:00.000",
+			ColumnKind::TimeDelta => "+00:00:00.000",
+			ColumnKind::Severity => "WRN",
+			ColumnKind::Source => "SourceNameAbcdef",
+			ColumnKind::CustomField(_) => "0000000000",
+			ColumnKind::Message => "",
+		}
+	}
+}
+
+pub fn default_columns(custom_fields: &[String]) -> Vec<Column> {
+	let mut columns = vec![
+		Column::new(ColumnKind::Timestamp, true),
+		Column::new(ColumnKind::TimeDelta, false),
+		Column::new(ColumnKind::Severity, true),
+		Column::new(ColumnKind::Source, false),
+	];
+	for name in custom_fields {
+		columns.push(Column::new(ColumnKind::CustomField(name.clone()), false));
+	}
+	columns.push(Column::new(ColumnKind::Message, true));
+	columns
+}
+
+pub fn short_severity(severity: &model::LogLevel) -> &'static str {
+	match severity {
+		model::LogLevel::Critical => "CRI",
+		model::LogLevel::Error => "ERR",
+		model::LogLevel::Warning => "WRN",
+		model::LogLevel::Info => "INF",
+		model::LogLevel::Debug => "DBG",
+		model::LogLevel::Trace => "TRC",
+	}
+}
+
+//Compact time difference, e.g. `+0.250`, `+1:02.003`, `-3:00:00.000` or `+2D 3:00:00.000`
+pub fn format_timediff_short(timediff: chrono::Duration) -> String {
+	let sign = if timediff.num_milliseconds() < 0 {
+		'-'
+	} else {
+		'+'
+	};
+	let mut timediff_ms = i64::abs(timediff.num_milliseconds());
+	let days = timediff_ms / 86_400_000;
+	timediff_ms -= days * 86_400_000;
+	let hours = timediff_ms / 3_600_000;
+	timediff_ms -= hours * 3_600_000;
+	let minutes = timediff_ms / 60_000;
+	timediff_ms -= minutes * 60_000;
+	let seconds = timediff_ms / 1000;
+	let milliseconds = timediff_ms - seconds * 1000;
+	if days > 0 {
+		format!(
+			"{}{}D {}:{:02}:{:02}.{:03}",
+			sign, days, hours, minutes, seconds, milliseconds
+		)
+	} else if hours > 0 {
+		format!(
+			"{}{}:{:02}:{:02}.{:03}",
+			sign, hours, minutes, seconds, milliseconds
+		)
+	} else if minutes > 0 {
+		format!("{}{}:{:02}.{:03}", sign, minutes, seconds, milliseconds)
+	} else {
+		format!("{}{}.{:03}", sign, seconds, milliseconds)
+	}
+}
+
+//Uptime of a boot-relative timestamp, e.g. `+00:03:12.441 since boot` or `+3D 01:00:00.000 since boot`
+pub fn format_uptime(uptime: chrono::Duration) -> String {
+	let mut uptime_ms = uptime.num_milliseconds().max(0);
+	let days = uptime_ms / 86_400_000;
+	uptime_ms -= days * 86_400_000;
+	let hours = uptime_ms / 3_600_000;
+	uptime_ms -= hours * 3_600_000;
+	let minutes = uptime_ms / 60_000;
+	uptime_ms -= minutes * 60_000;
+	let seconds = uptime_ms / 1000;
+	let milliseconds = uptime_ms - seconds * 1000;
+	if days > 0 {
+		format!(
+			"+{}D {:02}:{:02}:{:02}.{:03} since boot",
+			days, hours, minutes, seconds, milliseconds
+		)
+	} else {
+		format!(
+			"+{:02}:{:02}:{:02}.{:03} since boot",
+			hours, minutes, seconds, milliseconds
+		)
+	}
+}
+
+//Timestamp of an entry, boot-relative ones are shown as uptime instead of a date in 1970
+pub fn format_entry_timestamp(
+	timestamp: Timestamp,
+	format: &str,
+	time_zone: &settings::TimeZone,
+) -> String {
+	if timestamp.is_boot_relative() {
+		format_uptime(timestamp.uptime())
+	} else {
+		settings::format_timestamp(&timestamp.to_datetime(), format, time_zone)
+	}
+}
+
+//Text of a cell in the log view
+pub fn cell_text<'a>(kind: &ColumnKind, store: &'a LogStoreLinear, offset: usize) -> Cow<'a, str> {
+	let entry = &store.store[offset];
+	match kind {
+		ColumnKind::Timestamp => Cow::Owned(format_entry_timestamp(
+			entry.timestamp,
+			&store.timestamp_format,
+			&store.time_zone,
+		)),
+		ColumnKind::TimeDelta => {
+			//Note: The first element points to itself, resulting in +0.000
+			let prev = &store.store[entry.prev_offset as usize];
+			Cow::Owned(format_timediff_short(entry.timestamp - prev.timestamp))
+		}
+		ColumnKind::Severity => Cow::Borrowed(short_severity(&entry.severity)),
+		ColumnKind::Source => Cow::Borrowed(store.source_short_name(entry.source_id)),
+		ColumnKind::CustomField(name) => entry
+			.custom_field(name)
+			.map_or(Cow::Borrowed(""), |field| Cow::Owned(field.to_string())),
+		ColumnKind::Message => Cow::Borrowed(store.message(offset)),
+	}
+}
+
+//Replace control characters: Pango expands tabs to tab stops and draws others, such as
+//escape sequences or carriage returns, as hex boxes. Keeps the number of characters
+//intact, as wrapping is based on it.
+pub fn printable(text: &str) -> Cow<'_, str> {
+	if text.chars().any(char::is_control) {
+		Cow::Owned(
+			text.chars()
+				.map(|c| if c.is_control() { ' ' } else { c })
+				.collect(),
+		)
+	} else {
+		Cow::Borrowed(text)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::prelude::*;
+
+	#[test]
+	fn test_format_timediff_short() {
+		let ms = chrono::Duration::milliseconds;
+		assert_eq!(format_timediff_short(ms(0)), "+0.000");
+		assert_eq!(format_timediff_short(ms(250)), "+0.250");
+		assert_eq!(format_timediff_short(ms(62_003)), "+1:02.003");
+		assert_eq!(format_timediff_short(ms(-10_800_000)), "-3:00:00.000");
+		assert_eq!(format_timediff_short(ms(183_600_000)), "+2D 3:00:00.000");
+	}
+
+	#[test]
+	fn test_format_uptime() {
+		let ms = chrono::Duration::milliseconds;
+		assert_eq!(format_uptime(ms(192_441)), "+00:03:12.441 since boot");
+		assert_eq!(
+			format_uptime(ms(262_800_000)),
+			"+3D 01:00:00.000 since boot"
+		);
+		let boot_relative = Timestamp::from_datetime(&Utc.timestamp_opt(192, 441_000_000).unwrap());
+		assert!(boot_relative.is_boot_relative());
+		assert_eq!(
+			format_entry_timestamp(boot_relative, "%Y", &settings::TimeZone::Utc),
+			"+00:03:12.441 since boot"
+		);
+		let corrected = Utc.with_ymd_and_hms(2023, 7, 1, 0, 0, 0).unwrap();
+		assert!(!Timestamp::from_datetime(&corrected).is_boot_relative());
+		assert_eq!(
+			format_entry_timestamp(
+				Timestamp::from_datetime(&corrected),
+				"%Y",
+				&settings::TimeZone::Utc
+			),
+			"2023"
+		);
+	}
+}
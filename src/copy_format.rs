@@ -0,0 +1,255 @@
+use crate::columns;
+use crate::log_store::LogStoreLinear;
+
+// Clipboard format of log entries. The template contains placeholders in
+// curly braces, everything else is copied literally:
+//   {timestamp} {severity} {source} {path} {message} {field:<name>}
+// e.g. `{timestamp} | {severity} | {source} | {message}`
+
+pub const DEFAULT_TEMPLATE: &str = "{timestamp} | {severity} | {source} | {message}";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CopyField {
+	Timestamp,
+	Severity,
+	Source, //Last component of the source path
+	Path,   //Full source path
+	Message,
+	CustomField(String),
+}
+
+impl CopyField {
+	fn from_placeholder(placeholder: &str) -> Option<CopyField> {
+		match placeholder {
+			"timestamp" => Some(CopyField::Timestamp),
+			"severity" => Some(CopyField::Severity),
+			"source" => Some(CopyField::Source),
+			"path" => Some(CopyField::Path),
+			"message" => Some(CopyField::Message),
+			_ => placeholder
+				.strip_prefix("field:")
+				.map(|name| CopyField::CustomField(name.to_string())),
+		}
+	}
+
+	pub fn title(&self) -> &str {
+		match self {
+			CopyField::Timestamp => "Timestamp",
+			CopyField::Severity => "Severity",
+			CopyField::Source => "Source",
+			CopyField::Path => "Path",
+			CopyField::Message => "Message",
+			CopyField::CustomField(name) => name,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+	Literal(String),
+	Field(CopyField),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+	Native,
+	Lf,
+	CrLf,
+}
+
+impl LineEnding {
+	pub const ALL: [LineEnding; 3] = [LineEnding::Native, LineEnding::Lf, LineEnding::CrLf];
+
+	//Stable identifier, used in GUI combo boxes and settings files
+	pub fn id(&self) -> &'static str {
+		match self {
+			LineEnding::Native => "native",
+			LineEnding::Lf => "lf",
+			LineEnding::CrLf => "crlf",
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			LineEnding::Native => "Native",
+			LineEnding::Lf => "LF (Unix)",
+			LineEnding::CrLf => "CRLF (Windows)",
+		}
+	}
+
+	pub fn from_id(id: &str) -> Option<LineEnding> {
+		LineEnding::ALL
+			.iter()
+			.find(|ending| ending.id() == id)
+			.copied()
+	}
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			LineEnding::Native if cfg!(windows) => "\r\n",
+			LineEnding::Native | LineEnding::Lf => "\n",
+			LineEnding::CrLf => "\r\n",
+		}
+	}
+}
+
+pub struct CopyFormat {
+	segments: Vec<Segment>,
+	pub timestamp_format: String, //chrono format, including fractional seconds
+	pub line_ending: LineEnding,
+}
+
+impl CopyFormat {
+	pub fn new(template: &str, timestamp_format: &str, line_ending: LineEnding) -> CopyFormat {
+		CopyFormat {
+			segments: parse_template(template),
+			timestamp_format: timestamp_format.to_string(),
+			line_ending,
+		}
+	}
+
+	fn fields(&self) -> impl Iterator<Item = &CopyField> {
+		self.segments.iter().filter_map(|segment| match segment {
+			Segment::Field(field) => Some(field),
+			Segment::Literal(_) => None,
+		})
+	}
+
+	fn field_text(&self, field: &CopyField, store: &LogStoreLinear, offset: usize) -> String {
+		let entry = &store.store[offset];
+		match field {
+			CopyField::Timestamp => columns::format_entry_timestamp(
+				entry.timestamp,
+				&self.timestamp_format,
+				&store.time_zone,
+			),
+			CopyField::Severity => entry.severity.to_string(),
+			CopyField::Source => store.source_short_name(entry.source_id).to_string(),
+			CopyField::Path => store
+				.log_sources
+				.get(&entry.source_id)
+				.cloned()
+				.unwrap_or_default(),
+			CopyField::Message => store.message(offset).to_string(),
+			CopyField::CustomField(name) => entry
+				.custom_field(name)
+				.map(|value| value.to_string())
+				.unwrap_or_default(),
+		}
+	}
+
+	pub fn format_entry(&self, store: &LogStoreLinear, offset: usize) -> String {
+		let mut text = String::new();
+		for segment in &self.segments {
+			match segment {
+				Segment::Literal(literal) => text += literal,
+				Segment::Field(field) => text += &self.field_text(field, store, offset),
+			}
+		}
+		text
+	}
+
+	pub fn format_entries(&self, store: &LogStoreLinear, offsets: &[usize]) -> String {
+		let mut text = String::new();
+		for offset in offsets {
+			text += &self.format_entry(store, *offset);
+			text += self.line_ending.as_str();
+		}
+		text
+	}
+
+	//Table with one column per placeholder of the template, literals are left out
+	pub fn format_markdown(&self, store: &LogStoreLinear, offsets: &[usize]) -> String {
+		let line_ending = self.line_ending.as_str();
+		let fields: Vec<&CopyField> = self.fields().collect();
+		let mut text = String::new();
+		text += "|";
+		for field in &fields {
+			text += &format!(" {} |", escape_markdown_cell(field.title()));
+		}
+		text += line_ending;
+		text += "|";
+		for _ in &fields {
+			text += " --- |";
+		}
+		text += line_ending;
+		for offset in offsets {
+			text += "|";
+			for field in &fields {
+				let cell = self.field_text(field, store, *offset);
+				text += &format!(" {} |", escape_markdown_cell(&cell));
+			}
+			text += line_ending;
+		}
+		text
+	}
+}
+
+fn parse_template(template: &str) -> Vec<Segment> {
+	let mut segments = Vec::new();
+	let mut literal = String::new();
+	let mut rest = template;
+	while let Some(start) = rest.find('{') {
+		literal += &rest[..start];
+		let after = &rest[start + 1..];
+		match after
+			.find('}')
+			.and_then(|end| CopyField::from_placeholder(&after[..end]).map(|field| (end, field)))
+		{
+			Some((end, field)) => {
+				if !literal.is_empty() {
+					segments.push(Segment::Literal(std::mem::take(&mut literal)));
+				}
+				segments.push(Segment::Field(field));
+				rest = &after[end + 1..];
+			}
+			None => {
+				//Not a placeholder, keep the brace
+				literal.push('{');
+				rest = after;
+			}
+		}
+	}
+	literal += rest;
+	if !literal.is_empty() {
+		segments.push(Segment::Literal(literal));
+	}
+	segments
+}
+
+fn escape_markdown_cell(text: &str) -> String {
+	text.trim_end()
+		.replace('|', "\\|")
+		.replace("\r\n", "<br>")
+		.replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_template() {
+		assert_eq!(
+			parse_template("{timestamp} | {message} {field:PID} {unknown} {"),
+			vec![
+				Segment::Field(CopyField::Timestamp),
+				Segment::Literal(" | ".to_string()),
+				Segment::Field(CopyField::Message),
+				Segment::Literal(" ".to_string()),
+				Segment::Field(CopyField::CustomField("PID".to_string())),
+				Segment::Literal(" {unknown} {".to_string()),
+			]
+		);
+		assert_eq!(parse_template(""), vec![]);
+	}
+
+	#[test]
+	fn test_escape_markdown_cell() {
+		assert_eq!(escape_markdown_cell("a | b"), "a \\| b");
+		assert_eq!(
+			escape_markdown_cell("line 1\r\nline 2\n"),
+			"line 1<br>line 2"
+		);
+	}
+}
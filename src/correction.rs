@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use crate::message_arena::MessageArena;
+use crate::model;
+use crate::model_internal::LogEntryExt;
+
+// Corrections of boot-relative timestamps, see `Timestamp::is_boot_relative`.
+// Devices that only know their uptime log the offset to real time once they
+// get it, e.g. `Setting EtherCAT time [delta = 1562060032100954112 ns].`
+// Going backwards through a source, the boot-relative entries of the same
+// session before such a message are corrected by its delta. Rules are read
+// from the `[[timestamp_correction]]` tables of the settings.
+
+#[derive(Clone)]
+pub struct CorrectionRule {
+	pub name: String,
+	pub pattern: regex::Regex, //message with the offset, groups `delta` and optionally `unit`
+	pub unit: String,          //unit of the delta if the pattern has no `unit` group
+	pub resolution: i64,       //deltas are truncated to a multiple of this many ns
+	pub sources: regex::Regex, //paths of the sources to correct, e.g. `/x.sfile/Sensor/y.glog`
+	pub session_field: String, //custom field telling sessions apart, offsets only apply within one
+}
+
+//Entries corrected by all rules, see `find_corrections`
+#[derive(Debug, Default, PartialEq)]
+pub struct Corrections {
+	pub deltas: Vec<(u32, i64)>, //file position (see `merge_sources`) and delta in ns, ascending
+	pub counts: Vec<usize>,      //corrected entries per rule
+}
+
+//EtherCAT time of the sfile tree categories with `correct_timestamps` (sensors, connect boxes and
+//probes by default, see `SfileLayout`). Some log entries omit the dot at the end, both are accepted.
+//Like the GCOM timestamps it corrects, the delta is applied in 100 ns ticks.
+pub fn default_rules(categories: &[&str]) -> Vec<CorrectionRule> {
+	if categories.is_empty() {
+		return Vec::new();
+	}
+	let categories: Vec<String> = categories.iter().map(|name| regex::escape(name)).collect();
+	vec![CorrectionRule {
+		name: "EtherCAT time".to_string(),
+		pattern: regex::Regex::new(
+			r"^Setting EtherCAT time \[delta = (?P<delta>-?\d+) (?P<unit>ns|us|µs|ms|s)\]\.?$",
+		)
+		.unwrap(),
+		unit: "ns".to_string(),
+		resolution: 100,
+		sources: regex::Regex::new(&format!(r"^/[^/]*/({})/", categories.join("|"))).unwrap(),
+		session_field: "SessionId".to_string(),
+	}]
+}
+
+fn unit_nanos(unit: &str) -> Option<i64> {
+	match unit {
+		"ns" => Some(1),
+		"us" | "µs" => Some(1_000),
+		"ms" => Some(1_000_000),
+		"s" => Some(1_000_000_000),
+		_ => None,
+	}
+}
+
+impl CorrectionRule {
+	//None if a value is missing or invalid, the rule is skipped then
+	pub fn from_toml(table: &toml::Table) -> Option<CorrectionRule> {
+		let string = |name: &str| table.get(name).and_then(|v| v.as_str());
+		let pattern = regex::Regex::new(string("pattern")?).ok()?;
+		if !pattern.capture_names().any(|name| name == Some("delta")) {
+			return None;
+		}
+		let unit = string("unit").unwrap_or("ns");
+		unit_nanos(unit)?;
+		let resolution = match table.get("resolution") {
+			Some(resolution) => resolution.as_integer().filter(|ns| *ns > 0)?,
+			None => 1,
+		};
+		Some(CorrectionRule {
+			name: string("name").unwrap_or(pattern.as_str()).to_string(),
+			unit: unit.to_string(),
+			resolution,
+			sources: regex::Regex::new(string("sources").unwrap_or("")).ok()?,
+			session_field: string("session_field")?.to_string(),
+			pattern,
+		})
+	}
+
+	pub fn to_toml(&self) -> toml::Table {
+		let mut table = toml::Table::new();
+		table.insert("name".to_string(), self.name.clone().into());
+		table.insert("pattern".to_string(), self.pattern.as_str().into());
+		table.insert("unit".to_string(), self.unit.clone().into());
+		table.insert("resolution".to_string(), self.resolution.into());
+		table.insert("sources".to_string(), self.sources.as_str().into());
+		table.insert(
+			"session_field".to_string(),
+			self.session_field.clone().into(),
+		);
+		table
+	}
+
+	//None if `message` does not announce an offset, Some(None) if its delta is malformed
+	fn delta(&self, message: &str) -> Option<Option<i64>> {
+		let captures = self.pattern.captures(message)?;
+		let unit = captures
+			.name("unit")
+			.map_or(self.unit.as_str(), |unit| unit.as_str());
+		Some(
+			captures["delta"]
+				.parse::<i64>()
+				.ok()
+				.zip(unit_nanos(unit))
+				.and_then(|(delta, nanos)| delta.checked_mul(nanos))
+				.map(|delta| delta / self.resolution * self.resolution),
+		)
+	}
+}
+
+//Deltas of the boot-relative entries of `sources` (in the order of the tree, each in file order).
+//An entry is corrected by the first rule that applies.
+pub fn find_corrections(
+	rules: &[CorrectionRule],
+	sources: &[Vec<LogEntryExt>],
+	source_paths: &HashMap<u32, String>,
+	messages: &MessageArena,
+) -> Corrections {
+	let mut corrections = Corrections {
+		deltas: Vec::new(),
+		counts: vec![0; rules.len()],
+	};
+	let mut first_position = 0;
+	for entries in sources {
+		let path = entries
+			.first()
+			.and_then(|entry| source_paths.get(&entry.source_id))
+			.map_or("", |path| path.as_str());
+		let mut deltas: Vec<Option<i64>> = vec![None; entries.len()];
+		for (rule, count) in rules.iter().zip(corrections.counts.iter_mut()) {
+			if !rule.sources.is_match(path) {
+				continue;
+			}
+			log::info!(
+				"Correcting timestamps of {} with rule {:?}",
+				path,
+				rule.name
+			);
+			//Session and delta
+			let mut active: Option<(&model::CustomField, i64)> = None;
+			// Reverse iterate, from the newest to the oldest entry
+			for (idx, entry) in entries.iter().enumerate().rev() {
+				let session = match entry.custom_field(&rule.session_field) {
+					Some(session) => session,
+					None => {
+						active = None;
+						continue;
+					}
+				};
+				match rule.delta(messages.get(entry.message)) {
+					Some(Some(delta)) => {
+						if active.is_some_and(|(active_session, _)| active_session == session) {
+							log::warn!(
+								"Overwriting offset of session {} with {} ns",
+								session,
+								delta
+							);
+						}
+						active = Some((session, delta));
+					}
+					Some(None) => {
+						log::warn!("Could not parse offset: {}", messages.get(entry.message));
+						active = None;
+					}
+					None => match active {
+						//A different session, its entries are older than the offset
+						Some((active_session, _)) if active_session != session => active = None,
+						Some((_, delta))
+							if entry.timestamp.is_boot_relative() && deltas[idx].is_none() =>
+						{
+							deltas[idx] = Some(delta);
+							*count += 1;
+						}
+						//Without offset, entries are either already real time before it is known,
+						//or the device never got it. The latter is normal, they stay boot-relative.
+						_ => (),
+					},
+				}
+			}
+		}
+		corrections.deltas.extend(
+			deltas
+				.iter()
+				.enumerate()
+				.filter_map(|(idx, delta)| Some((first_position + idx as u32, (*delta)?))),
+		);
+		first_position += entries.len() as u32;
+	}
+	corrections
+}
+
+//Applies (or with `sign` -1, reverts) the deltas to `sources` as passed to `find_corrections`
+pub fn apply(sources: &mut [Vec<LogEntryExt>], deltas: &[(u32, i64)], sign: i64) {
+	let mut deltas = deltas.iter().peekable();
+	for (position, entry) in sources.iter_mut().flatten().enumerate() {
+		let position = position as u32;
+		if let Some((_, delta)) = deltas.next_if(|(delta_position, _)| *delta_position == position)
+		{
+			entry.timestamp = entry.timestamp.offset(sign * delta);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::model_internal::Timestamp;
+	use chrono::prelude::*;
+
+	const CATEGORIES: [&str; 3] = ["Sensor", "Connect Box", "Probe"];
+
+	#[test]
+	fn test_find_corrections() {
+		let mut messages = crate::message_arena::MessageArenaBuilder::default();
+		let mut entry = |secs: i64, session: Option<u32>, message: &str| LogEntryExt {
+			timestamp: Timestamp::from_datetime(&Utc.timestamp_opt(secs, 0).unwrap()),
+			severity: model::LogLevel::Info,
+			message: messages.intern(message),
+			source_id: 1,
+			custom_fields: session
+				.map(|session| {
+					(
+						std::borrow::Cow::Borrowed("SessionId"),
+						model::CustomField::UInt32(session),
+					)
+				})
+				.into_iter()
+				.collect(),
+			visible: crate::model_internal::VISIBLE_ON,
+			entry_id: 0,
+			prev_offset: 0,
+			next_offset: 0,
+		};
+		let mut sources = vec![vec![
+			entry(5, Some(1), "no offset in this session"),
+			entry(10, Some(2), "boot"),
+			entry(20, Some(2), "Setting EtherCAT time [delta = 1600000000 s]"),
+			entry(1_600_000_030, Some(2), "already real time"),
+			entry(
+				40,
+				Some(2),
+				"Setting EtherCAT time [delta = 1600000000000 ms].",
+			),
+			entry(50, None, "special log without session"),
+		]];
+		let source_paths = HashMap::from([(1, "/log.sfile/Sensor/sensor.glog".to_string())]);
+		let messages = messages.finish();
+		let corrections = find_corrections(
+			&default_rules(&CATEGORIES),
+			&sources,
+			&source_paths,
+			&messages,
+		);
+		assert_eq!(
+			corrections,
+			Corrections {
+				deltas: vec![(1, 1_600_000_000_000_000_000)],
+				counts: vec![1],
+			}
+		);
+		let timestamps = |sources: &[Vec<LogEntryExt>]| -> Vec<i64> {
+			sources[0]
+				.iter()
+				.map(|entry| entry.datetime().timestamp())
+				.collect()
+		};
+		apply(&mut sources, &corrections.deltas, 1);
+		assert_eq!(
+			timestamps(&sources),
+			vec![5, 1_600_000_010, 20, 1_600_000_030, 40, 50]
+		);
+		apply(&mut sources, &corrections.deltas, -1);
+		assert_eq!(timestamps(&sources), vec![5, 10, 20, 1_600_000_030, 40, 50]);
+
+		//Out of scope
+		let source_paths = HashMap::from([(1, "/log.sfile/Client/client.glog".to_string())]);
+		let corrections = find_corrections(
+			&default_rules(&CATEGORIES),
+			&sources,
+			&source_paths,
+			&messages,
+		);
+		assert_eq!(corrections.deltas, vec![]);
+	}
+
+	#[test]
+	fn test_rule_from_toml() {
+		let rule = &default_rules(&CATEGORIES)[0];
+		let restored = CorrectionRule::from_toml(&rule.to_toml()).unwrap();
+		assert_eq!(restored.pattern.as_str(), rule.pattern.as_str());
+		assert_eq!(restored.sources.as_str(), rule.sources.as_str());
+		assert_eq!(
+			restored.delta("Setting EtherCAT time [delta = -3 us]"),
+			Some(Some(-3_000))
+		);
+		//Truncated to 100 ns ticks towards zero, like the GCOM timespans
+		assert_eq!(
+			restored.delta("Setting EtherCAT time [delta = 1562060032100954112 ns]."),
+			Some(Some(1_562_060_032_100_954_100))
+		);
+		assert_eq!(
+			restored.delta("Setting EtherCAT time [delta = -250 ns]"),
+			Some(Some(-200))
+		);
+		assert_eq!(
+			restored.delta("Setting EtherCAT time [delta = 99999999999 s]"),
+			Some(None)
+		);
+		assert_eq!(restored.delta("Setting EtherCAT time"), None);
+		let table = "pattern = 'offset (\\d+)'\nsession_field = 'SessionId'"
+			.parse::<toml::Table>()
+			.unwrap();
+		assert!(CorrectionRule::from_toml(&table).is_none()); //no `delta` group
+		let table = "pattern = 'offset (?P<delta>\\d+)'\nsession_field = 'SessionId'"
+			.parse::<toml::Table>()
+			.unwrap();
+		let rule = CorrectionRule::from_toml(&table).unwrap();
+		assert_eq!(rule.delta("offset 1234567"), Some(Some(1_234_567))); //full ns by default
+	}
+}
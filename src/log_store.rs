@@ -1,12 +1,26 @@
 extern crate chrono;
 
 use chrono::prelude::*;
+use std::collections::BTreeSet;
+use std::collections::BinaryHeap;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::sync::Arc;
 
+use gtk::glib;
+use gtk::pango;
+
+use crate::columns;
+use crate::copy_format;
+use crate::message_arena::MessageArena;
 use crate::model;
+use crate::search;
+use crate::settings;
+use crate::theme;
+use crate::timeline;
 
 use crate::model_internal::LogEntryExt;
+use crate::model_internal::Timestamp;
 
 pub struct ScrollBarVert {
 	pub x: f64,
@@ -26,11 +40,87 @@ pub struct ScrollBarVert {
 	pub scroll_perc: f64,
 }
 
+//Paging while the mouse button is held on the scroll bar track
+pub struct PageScroll {
+	pub direction: i64,                //-1: up, 1: down
+	pub pointer_y: f64,                //paging stops once the thumb reaches the pointer
+	pub timer: Option<glib::SourceId>, //repeats the paging, removed on release
+}
+
+pub struct ScrollBarHorz {
+	pub x: f64,
+	pub y: f64,
+
+	pub bar_padding: f64,
+	pub bar_width: f64,
+	pub bar_height: f64,
+
+	pub thumb_x: f64,
+	pub thumb_y: f64,
+	pub thumb_margin: f64,
+	pub thumb_width: f64,
+	pub thumb_height: f64,
+	pub thumb_rel_offset: f64,
+
+	pub scroll_perc: f64,
+}
+
+//Order of the entries in the store, see `LogStoreLinear::set_sort_mode`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortMode {
+	Timestamp, //then file order, so entries with identical timestamps keep their order
+	FileOrder, //sources in the order of the tree, entries in the order of their file
+	Session,   //by `SessionId` field, then file order; entries without session last
+}
+
+impl SortMode {
+	pub const ALL: [SortMode; 3] = [SortMode::Timestamp, SortMode::FileOrder, SortMode::Session];
+
+	//Stable identifier, used in GUI combo boxes and session files
+	pub fn id(&self) -> &'static str {
+		match self {
+			SortMode::Timestamp => "timestamp",
+			SortMode::FileOrder => "file",
+			SortMode::Session => "session",
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			SortMode::Timestamp => "Timestamp",
+			SortMode::FileOrder => "File order",
+			SortMode::Session => "Session, then file order",
+		}
+	}
+
+	pub fn from_id(id: &str) -> Option<SortMode> {
+		SortMode::ALL.iter().find(|mode| mode.id() == id).copied()
+	}
+}
+
+//Visible entries of interest within a block of consecutive entry ids, see `LogStoreLinear::density`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DensityBlock {
+	pub critical: u32,
+	pub error: u32,
+	pub warning: u32,
+	pub search_hits: u32,
+}
+
+//Upper bound of density blocks, enough for the pixel rows of any scroll bar
+const DENSITY_BLOCKS: usize = 4096;
+
 pub struct LogStoreLinear {
 	pub store: Vec<LogEntryExt>,
-	pub entry_count: usize,  //entry_count <= store.len(), number of active items
-	pub first_offset: usize, //first_offset < store.len(), offset of first active element in vec
-	pub last_offset: usize,  //last_offset < store.len(), offset of last active element in vec
+	pub file_positions: Vec<u32>, //position of each entry in the files, in the order of the tree
+	pub sort_mode: SortMode,
+	pub place_boot_relative: bool, //sort boot-relative entries after their session, see `set_place_boot_relative`
+	pub corrections: Vec<(u32, i64)>, //timestamp corrections by file position, see `correction`
+	pub corrections_enabled: bool, //whether `corrections` are applied to the timestamps
+	pub messages: Arc<MessageArena>, //text of the entries' messages, see `message`
+	pub entry_count: usize,        //entry_count <= store.len(), number of active items
+	pub first_offset: usize,       //first_offset < store.len(), offset of first active element in vec
+	pub last_offset: usize,        //last_offset < store.len(), offset of last active element in vec
 	pub anchor_offset: Option<usize>, //anchor_offset < store.len(), offset of anchor element that aligns GUI on visibility changes
 
 	pub show_crit: bool,
@@ -45,181 +135,943 @@ pub struct LogStoreLinear {
 	pub selected_single_last: Option<usize>,
 	pub selected_range: Option<(usize, usize)>,
 
+	pub bookmarks: BTreeSet<usize>, //offsets of bookmarked entries
+	pub expanded: HashSet<usize>,   //offsets of multi-line entries showing all their lines
+
+	pub field_filter: Option<(String, model::CustomField)>, //only show entries with this custom field value
+	pub time_range: Option<(DateTime<Utc>, DateTime<Utc>)>, //only show entries in this range (inclusive)
+	pub timeline: Option<timeline::Histogram>,              //cached, reset by `filter_store`
+	pub timeline_brush: Option<(f64, f64)>, //x positions while selecting a time range in the timeline
+
 	pub pressed_shift: bool,
 	pub pressed_ctrl: bool,
 
 	pub log_sources: HashMap<u32, String>,
+	pub source_colors: HashMap<u32, (f64, f64, f64)>, //user-assigned colors, see `source_color`
+
+	pub columns: Vec<columns::Column>,
+	pub column_drag: Option<usize>, //index of column whose right separator is being dragged
+	pub column_resize_cursor: bool,
+	pub header_height: f64,
 
 	pub visible_lines: usize, //visible entries in GUI (i.e. number of rows your text viewport has)
 	pub hover_line: Option<usize>, //line the mouse cursor hovers over, relative to viewport_offset
 	pub viewport_offset: usize, //viewport_offset < store.len(), offset of GUI viewport
+	pub viewport_row: usize,  //first visible row of the (wrapped) entry at viewport_offset
 	pub mouse_down: bool,
 	pub thumb_drag: bool,
 	pub thumb_drag_x: f64,
 	pub thumb_drag_y: f64,
 	pub scroll_bar: ScrollBarVert,
+	pub page_scroll: Option<PageScroll>,
+	pub search_text: String, //current search, marked in the density overview
+	pub density: Vec<DensityBlock>, //heat-map along the scroll bar, updated by `filter_store`
+	pub density_block_size: usize, //entry ids per density block
+
+	//Index lists of offsets (ascending) for incremental filtering, see `build_indices`
+	pub severity_index: [Vec<u32>; 6], //see `theme::severity_index`
+	pub source_index: HashMap<u32, Vec<u32>>,
+	pub search_cache: Vec<(String, Vec<u32>)>, //matches of the current search and the ones it narrowed
+	pub search_job: Option<search::SearchJob>, //search running in the background, see `start_search`
+	pub visible_offsets: Vec<u32>,             //offsets of the visible entries, indexed by entry id
+
+	pub wrap: bool,         //soft-wrap long messages instead of scrolling horizontally
+	pub wrap_chars: usize,  //characters per row of a wrapped message, 0 if not wrapping
+	pub h_offset: f64,      //horizontal scroll offset of the message column in pixels
+	pub content_width: f64, //width of the longest message in the viewport
+	pub h_thumb_drag: bool,
+	pub h_scroll_bar: ScrollBarHorz,
 
 	pub border_left: f64,
 	pub border_top: f64,
 	pub border_bottom: f64,
 	pub line_spacing: f64,
-	pub font_size: f64,
+	pub font: pango::FontDescription, //font of the log view, selected by the user
+	pub row_padding: f64,             //pixels above and below the text of each row
+	pub scroll_speed: i64,            //rows per scroll wheel step
+	pub timestamp_format: String,     //chrono format of the timestamp column
+	pub time_zone: settings::TimeZone,
+
+	pub theme_kind: theme::ThemeKind,
+	pub system_dark: bool, //GTK theme preference, used by `ThemeKind::System`
+	pub severity_overrides: [Option<theme::SeverityColors>; 6], //user-assigned, see `theme::severity_index`
+	pub theme: theme::Theme, //resolved from the fields above, see `update_theme`
+
+	pub copy_format: copy_format::CopyFormat,
+}
+
+//Qualitative color palette (Tableau 10) to tell log sources apart
+const SOURCE_PALETTE: [(f64, f64, f64); 10] = [
+	(0.122, 0.467, 0.706), //Blue
+	(1.000, 0.498, 0.055), //Orange
+	(0.173, 0.627, 0.173), //Green
+	(0.839, 0.153, 0.157), //Red
+	(0.580, 0.404, 0.741), //Purple
+	(0.549, 0.337, 0.294), //Brown
+	(0.890, 0.467, 0.761), //Pink
+	(0.498, 0.498, 0.498), //Grey
+	(0.737, 0.741, 0.133), //Olive
+	(0.090, 0.745, 0.812), //Cyan
+];
+
+pub fn color_to_hex(color: (f64, f64, f64)) -> String {
+	let to_u8 = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+	format!(
+		"#{:02x}{:02x}{:02x}",
+		to_u8(color.0),
+		to_u8(color.1),
+		to_u8(color.2)
+	)
+}
+
+pub fn color_from_hex(hex: &str) -> Option<(f64, f64, f64)> {
+	let hex = hex.strip_prefix('#')?;
+	if hex.len() != 6 {
+		return None;
+	}
+	let channel = |i: usize| {
+		hex.get(i..i + 2)
+			.and_then(|c| u8::from_str_radix(c, 16).ok())
+			.map(|c| c as f64 / 255.0)
+	};
+	Some((channel(0)?, channel(2)?, channel(4)?))
 }
 
 impl LogStoreLinear {
-	pub fn rel_to_abs_offset(&self, rel_offset: usize) -> Option<usize> {
-		self.store
+	//Color of a log source, either assigned by the user or picked from the palette
+	pub fn source_color(&self, source_id: u32) -> (f64, f64, f64) {
+		self.source_colors
+			.get(&source_id)
+			.copied()
+			.unwrap_or(SOURCE_PALETTE[source_id as usize % SOURCE_PALETTE.len()])
+	}
+
+	//Line (relative to viewport_offset) at the given y coordinate of the log view
+	pub fn line_at(&self, y: f64) -> Option<usize> {
+		let rows_top = self.border_top + self.header_height;
+		if y < rows_top {
+			return None;
+		}
+		let line = ((y - rows_top) / self.line_spacing) as usize;
+		if line < self.visible_lines {
+			Some(line)
+		} else {
+			None
+		}
+	}
+
+	//(index, x, width) of all visible columns. The message column extends to infinity.
+	pub fn column_positions(&self) -> Vec<(usize, f64, f64)> {
+		let mut positions = Vec::with_capacity(self.columns.len());
+		let mut x = self.border_left;
+		for (idx, column) in self.columns.iter().enumerate() {
+			if !column.visible {
+				continue;
+			}
+			if column.kind == columns::ColumnKind::Message {
+				positions.push((idx, x, f64::INFINITY));
+			} else {
+				positions.push((idx, x, column.width()));
+				x += column.width();
+			}
+		}
+		positions
+	}
+
+	pub fn column_at(&self, x: f64) -> Option<usize> {
+		self.column_positions()
+			.into_iter()
+			.find(|(_, column_x, width)| x >= *column_x && x < column_x + width)
+			.map(|(idx, _, _)| idx)
+	}
+
+	//Column whose right separator is at the given x coordinate, within a few pixels
+	pub fn column_separator_at(&self, x: f64) -> Option<usize> {
+		self.column_positions()
+			.into_iter()
+			.find(|(_, column_x, width)| width.is_finite() && (column_x + width - x).abs() <= 4.0)
+			.map(|(idx, _, _)| idx)
+	}
+
+	//Resolve the theme after changing its kind, the system preference or the severity colors
+	pub fn update_theme(&mut self) {
+		self.theme = theme::Theme::new(self.theme_kind, self.system_dark);
+		for (colors, user_colors) in self
+			.theme
+			.severity
+			.iter_mut()
+			.zip(self.severity_overrides.iter())
+		{
+			if let Some(user_colors) = user_colors {
+				*colors = *user_colors;
+			}
+		}
+	}
+
+	pub fn message(&self, abs_offset: usize) -> &str {
+		self.messages.get(self.store[abs_offset].message)
+	}
+
+	//Last component of the log source path, e.g. `Hwa` for `/logfile.sfile/Controller/Hwa`
+	pub fn source_short_name(&self, source_id: u32) -> &str {
+		self.log_sources
+			.get(&source_id)
+			.map(|path| path.rsplit('/').next().unwrap_or(path))
+			.unwrap_or("")
+	}
+
+	//Visible selected entries in store order; only the selection is walked, not the store
+	pub fn selected_offsets(&self) -> Vec<usize> {
+		let mut offsets: Vec<usize> = self.selected_single.iter().copied().collect();
+		if let Some((first, last)) = self.selected_range {
+			offsets.extend(first..=last.min(self.store.len().saturating_sub(1)));
+		}
+		offsets.sort_unstable();
+		offsets.dedup();
+		offsets.retain(|offset| {
+			self.store[*offset].is_visible() && !self.excluded_single.contains(offset)
+		});
+		offsets
+	}
+
+	//True if entries can take up more than one visual row, i.e. scrolling works in rows
+	pub fn rows_vary(&self) -> bool {
+		self.wrap_chars > 0 || !self.expanded.is_empty()
+	}
+
+	//Lines of a message as displayed: Only the first one unless the entry is expanded
+	pub fn entry_lines(&self, abs_offset: usize) -> impl Iterator<Item = &str> {
+		let message = self.message(abs_offset);
+		let take = if self.expanded.contains(&abs_offset) {
+			usize::MAX
+		} else {
+			1
+		};
+		//Note: An empty message has no lines, but still takes up one row
+		std::iter::once(message.lines().next().unwrap_or(""))
+			.chain(message.lines().skip(1))
+			.take(take)
+	}
+
+	//Number of lines hidden by the collapsed form of a multi-line entry
+	pub fn hidden_lines(&self, abs_offset: usize) -> usize {
+		if self.expanded.contains(&abs_offset) {
+			0
+		} else {
+			self.message(abs_offset).lines().skip(1).count()
+		}
+	}
+
+	//Number of visual rows an entry takes up in the log view
+	pub fn entry_rows(&self, abs_offset: usize) -> usize {
+		if !self.rows_vary() {
+			return 1; //Skip looking at the message
+		}
+		self.entry_lines(abs_offset)
+			.map(|line| {
+				if self.wrap_chars == 0 {
+					1
+				} else {
+					std::cmp::max(1, line.chars().count().div_ceil(self.wrap_chars))
+				}
+			})
+			.sum()
+	}
+
+	//Text of visual row `row` of an entry, see `entry_rows`
+	pub fn entry_row(&self, abs_offset: usize, mut row: usize) -> &str {
+		for line in self.entry_lines(abs_offset) {
+			let line_rows = if self.wrap_chars == 0 {
+				1
+			} else {
+				std::cmp::max(1, line.chars().count().div_ceil(self.wrap_chars))
+			};
+			if row < line_rows {
+				return wrapped_row(line, self.wrap_chars, row);
+			}
+			row -= line_rows;
+		}
+		""
+	}
+
+	//Expand or collapse a multi-line entry, returns false if the entry has only one line
+	pub fn toggle_expanded(&mut self, abs_offset: usize) -> bool {
+		if !self.expanded.remove(&abs_offset) {
+			if self.message(abs_offset).lines().nth(1).is_none() {
+				return false;
+			}
+			self.expanded.insert(abs_offset);
+		}
+		//The viewport may start inside the entry, which changed its number of rows
+		self.viewport_row = self.first_row(self.viewport_offset);
+		true
+	}
+
+	//True if all entries fit on screen, i.e. there is nothing to scroll
+	pub fn fits_on_screen(&self, window_size: usize) -> bool {
+		if !self.rows_vary() {
+			return self.entry_count <= window_size;
+		}
+		self.viewport_offset == self.first_offset
+			&& self.viewport_row == 0
+			&& self.rows_from_viewport(window_size + 1) <= window_size
+	}
+
+	//Id of the first visible entry at or after `abs_offset`, entry_count if there is none
+	pub fn entry_id_at(&self, abs_offset: usize) -> usize {
+		match self.store.get(abs_offset) {
+			Some(entry) if entry.is_visible() => entry.entry_id as usize,
+			_ => self
+				.visible_offsets
+				.partition_point(|offset| (*offset as usize) < abs_offset),
+		}
+	}
+
+	//Offsets of the visible entries, starting at the first one at or after `abs_offset`
+	pub fn visible_from(&self, abs_offset: usize) -> impl Iterator<Item = usize> + '_ {
+		self.visible_offsets[self.entry_id_at(abs_offset)..]
 			.iter()
-			.enumerate() //offset in vector
-			.skip(self.viewport_offset)
-			.filter(|(_, x)| x.is_visible())
-			.skip(rel_offset)
-			.take(1)
-			.next()
-			.map(|(offset, _)| offset)
+			.map(|offset| *offset as usize)
 	}
 
+	//Visual rows from the top of the viewport to the end of the list, counting at most `limit`
+	fn rows_from_viewport(&self, limit: usize) -> usize {
+		if !self.rows_vary() {
+			let entries = self.entry_count - self.entry_id_at(self.viewport_offset);
+			return std::cmp::min(entries, limit);
+		}
+		let mut rows = 0;
+		for offset in self.visible_from(self.viewport_offset) {
+			rows += self.entry_rows(offset) - self.first_row(offset);
+			if rows >= limit {
+				break;
+			}
+		}
+		std::cmp::min(rows, limit)
+	}
+
+	//First visible row of an entry, only nonzero for a wrapped entry cut off at the top
+	pub fn first_row(&self, abs_offset: usize) -> usize {
+		if abs_offset == self.viewport_offset {
+			std::cmp::min(self.viewport_row, self.entry_rows(abs_offset) - 1)
+		} else {
+			0
+		}
+	}
+
+	//Maps a visual row, relative to the viewport, to the entry displayed in that row
+	pub fn rel_to_abs_offset(&self, rel_offset: usize) -> Option<usize> {
+		if !self.rows_vary() {
+			return self
+				.visible_offsets
+				.get(self.entry_id_at(self.viewport_offset) + rel_offset)
+				.map(|offset| *offset as usize);
+		}
+		let mut rows = 0;
+		for offset in self.visible_from(self.viewport_offset) {
+			rows += self.entry_rows(offset) - self.first_row(offset);
+			if rel_offset < rows {
+				return Some(offset);
+			}
+		}
+		None
+	}
+
+	//Maps an entry to the first visual row it is displayed in, relative to the viewport
 	pub fn abs_to_rel_offset(&self, abs_offset: usize) -> Option<usize> {
-		for (i, (offset, _)) in self
+		if !self.rows_vary() {
+			let entry = self.store.get(abs_offset)?;
+			let viewport_id = self.entry_id_at(self.viewport_offset);
+			return (entry.entry_id as usize)
+				.checked_sub(viewport_id)
+				.filter(|rel_offset| entry.is_visible() && *rel_offset < self.visible_lines);
+		}
+		let mut rows = 0;
+		for offset in self.visible_from(self.viewport_offset) {
+			if rows >= self.visible_lines {
+				break;
+			}
+			if offset == abs_offset {
+				return Some(rows);
+			}
+			rows += self.entry_rows(offset) - self.first_row(offset);
+		}
+		None
+	}
+
+	pub fn set_field_filter(&mut self, field_filter: Option<(String, model::CustomField)>) {
+		match &field_filter {
+			Some((name, value)) => {
+				self.filter_store(
+					&|entry: &LogEntryExt| entry.custom_field(name) == Some(value),
+					true,
+					crate::model_internal::VISIBLE_OFF_FIELD,
+				);
+				self.filter_store(
+					&|entry: &LogEntryExt| entry.custom_field(name) != Some(value),
+					false,
+					crate::model_internal::VISIBLE_OFF_FIELD,
+				);
+			}
+			None => self.filter_store(
+				&|_entry: &LogEntryExt| true,
+				true,
+				crate::model_internal::VISIBLE_OFF_FIELD,
+			),
+		}
+		self.field_filter = field_filter;
+	}
+
+	//Index lists for incremental filtering and the initial links; call once the store is sorted
+	pub fn build_indices(&mut self) {
+		self.build_index_lists();
+		self.search_cache.clear();
+		self.search_job = None;
+		self.density_block_size = std::cmp::max(1, self.store.len().div_ceil(DENSITY_BLOCKS));
+		let anchor = self.anchor_position();
+		self.relink(Some(0), anchor);
+	}
+
+	fn build_index_lists(&mut self) {
+		self.severity_index = Default::default();
+		self.source_index.clear();
+		for (offset, entry) in self.store.iter().enumerate() {
+			self.severity_index[theme::severity_index(&entry.severity)].push(offset as u32);
+			self.source_index
+				.entry(entry.source_id)
+				.or_default()
+				.push(offset as u32);
+		}
+	}
+
+	//Reorders the store. Anchor, bookmarks and expanded entries move along with their entries,
+	//the selection is cleared as its ranges would not make sense anymore.
+	pub fn set_sort_mode(&mut self, sort_mode: SortMode) {
+		if sort_mode == self.sort_mode {
+			return;
+		}
+		self.sort_mode = sort_mode;
+		self.reorder();
+	}
+
+	//Boot-relative entries (never corrected with EtherCAT time) are sorted as if they were logged
+	//right after the last entry of the same session with a real timestamp, instead of in 1970.
+	//Only affects the timestamp sort mode.
+	pub fn set_place_boot_relative(&mut self, place_boot_relative: bool) {
+		if place_boot_relative == self.place_boot_relative {
+			return;
+		}
+		self.place_boot_relative = place_boot_relative;
+		if self.sort_mode == SortMode::Timestamp {
+			self.reorder();
+		}
+	}
+
+	//Applies or reverts the timestamp corrections, e.g. to compare with the raw timestamps
+	pub fn set_corrections_enabled(&mut self, enabled: bool) {
+		if enabled == self.corrections_enabled {
+			return;
+		}
+		self.apply_corrections(enabled);
+		if self.sort_mode == SortMode::Timestamp {
+			self.reorder();
+		}
+		//Entries may have moved into or out of the time range
+		self.mask_time_range(self.time_range);
+	}
+
+	//Shifts the timestamps only, the caller reorders the store
+	fn apply_corrections(&mut self, enabled: bool) {
+		self.corrections_enabled = enabled;
+		let sign = if enabled { 1 } else { -1 };
+		let deltas: HashMap<u32, i64> = self.corrections.iter().copied().collect();
+		for (entry, file_position) in self.store.iter_mut().zip(&self.file_positions) {
+			if let Some(delta) = deltas.get(file_position) {
+				entry.timestamp = entry.timestamp.offset(sign * delta);
+			}
+		}
+		self.timeline = None;
+	}
+
+	//Sort mode, placement and corrections of a session at once, so that the store is reordered
+	//and relinked only once instead of by each setter
+	pub fn restore_order(
+		&mut self,
+		sort_mode: SortMode,
+		place_boot_relative: bool,
+		corrections_enabled: bool,
+	) {
+		let corrections_changed = corrections_enabled != self.corrections_enabled;
+		if corrections_changed {
+			self.apply_corrections(corrections_enabled);
+		}
+		let timestamp_order_changed =
+			corrections_changed || place_boot_relative != self.place_boot_relative;
+		let reorder = sort_mode != self.sort_mode
+			|| (sort_mode == SortMode::Timestamp && timestamp_order_changed);
+		self.sort_mode = sort_mode;
+		self.place_boot_relative = place_boot_relative;
+		if reorder {
+			self.reorder();
+		}
+		if corrections_changed && self.time_range.is_some() {
+			self.mask_time_range(self.time_range);
+		}
+	}
+
+	//Timestamp of an entry as loaded, i.e. corrected even while corrections are disabled, so that
+	//it does not change while investigating; see `session::EntryKey`
+	pub fn key_timestamp(&self, abs_offset: usize) -> Timestamp {
+		let mut timestamp = self.store[abs_offset].timestamp;
+		if !self.corrections_enabled {
+			let file_position = self.file_positions[abs_offset];
+			if let Ok(idx) = self
+				.corrections
+				.binary_search_by_key(&file_position, |(position, _)| *position)
+			{
+				timestamp = timestamp.offset(self.corrections[idx].1);
+			}
+		}
+		timestamp
+	}
+
+	//Whether the entries are in timestamp order, so that time ranges can be found by binary search
+	fn sorted_by_timestamp(&self) -> bool {
+		self.sort_mode == SortMode::Timestamp && !self.place_boot_relative
+	}
+
+	//Timestamp to sort boot-relative entries by: the last real timestamp of their session
+	//(per source, sessions of different devices are unrelated). None for all other entries.
+	fn boot_relative_placement(&self) -> Vec<Option<Timestamp>> {
+		let session_key = |entry: &LogEntryExt| match entry.custom_field("SessionId") {
+			Some(model::CustomField::UInt32(session_id)) => Some((entry.source_id, *session_id)),
+			_ => None,
+		};
+		let mut session_ends: HashMap<(u32, u32), Timestamp> = HashMap::new();
+		for entry in self
 			.store
 			.iter()
-			.enumerate() //offset in vector
-			.skip(self.viewport_offset)
-			.filter(|(_, x)| x.is_visible())
-			.take(self.visible_lines)
-			//index of filtered element:
-			.enumerate()
+			.filter(|entry| !entry.timestamp.is_boot_relative())
 		{
-			if offset == abs_offset {
-				return Some(i);
+			if let Some(key) = session_key(entry) {
+				let end = session_ends.entry(key).or_insert(entry.timestamp);
+				*end = std::cmp::max(*end, entry.timestamp);
 			}
 		}
-		None
+		self.store
+			.iter()
+			.map(|entry| match entry.timestamp.is_boot_relative() {
+				true => session_key(entry).and_then(|key| session_ends.get(&key).copied()),
+				false => None,
+			})
+			.collect()
 	}
 
+	fn reorder(&mut self) {
+		let (anchor_offset, rel_offset) = self.anchor_position();
+		let mut order: Vec<u32> = (0..self.store.len() as u32).collect();
+		let placement = match self.sort_mode == SortMode::Timestamp && self.place_boot_relative {
+			true => self.boot_relative_placement(),
+			false => vec![None; self.store.len()],
+		};
+		{
+			let store = &self.store;
+			let file_positions = &self.file_positions;
+			match self.sort_mode {
+				//Placed entries come after all entries with the timestamp of their session's end
+				SortMode::Timestamp => order.sort_by_key(|offset| {
+					let offset = *offset as usize;
+					match placement[offset] {
+						Some(timestamp) => (timestamp, true, file_positions[offset]),
+						None => (store[offset].timestamp, false, file_positions[offset]),
+					}
+				}),
+				SortMode::FileOrder => order.sort_by_key(|offset| file_positions[*offset as usize]),
+				SortMode::Session => order.sort_by_key(|offset| {
+					let offset = *offset as usize;
+					let session_id = match store[offset].custom_field("SessionId") {
+						Some(model::CustomField::UInt32(session_id)) => Some(*session_id),
+						_ => None,
+					};
+					(session_id.is_none(), session_id, file_positions[offset])
+				}),
+			}
+		}
+		let mut new_offsets = vec![0; order.len()];
+		for (new_offset, old_offset) in order.iter().enumerate() {
+			new_offsets[*old_offset as usize] = new_offset;
+		}
+		permute(&mut self.file_positions, &mut order.clone());
+		permute(&mut self.store, &mut order);
+
+		let remap = |offset: usize| new_offsets[offset];
+		self.anchor_offset = self.anchor_offset.map(remap);
+		self.bookmarks = self.bookmarks.iter().map(|offset| remap(*offset)).collect();
+		self.expanded = self.expanded.iter().map(|offset| remap(*offset)).collect();
+		for (_, matches) in &mut self.search_cache {
+			for offset in matches.iter_mut() {
+				*offset = new_offsets[*offset as usize] as u32;
+			}
+			matches.sort_unstable();
+		}
+		self.selected_single.clear();
+		self.excluded_single.clear();
+		self.selected_single_last = None;
+		self.selected_range = None;
+
+		//Offsets of a running search are stale, search again
+		let search_text = self.search_job.take().map(|job| job.text.clone());
+		self.build_index_lists();
+		self.relink(Some(0), (remap(anchor_offset), rel_offset));
+		if let Some(search_text) = search_text {
+			self.start_search(&search_text);
+		}
+	}
+
+	pub fn set_severity_visible(&mut self, severity: &model::LogLevel, visible: bool) {
+		let anchor = self.anchor_position();
+		let first_changed = mask_entries(
+			&mut self.store,
+			&self.severity_index[theme::severity_index(severity)],
+			crate::model_internal::VISIBLE_OFF_SEVERITY,
+			!visible,
+		);
+		self.relink(first_changed, anchor);
+	}
+
+	//Log source ids of a subtree are contiguous, see `toggle_row`
+	pub fn set_sources_visible(&mut self, first_id: u32, last_id: u32, visible: bool) {
+		let anchor = self.anchor_position();
+		let mut first_changed = None;
+		for source_id in first_id..=last_id {
+			if let Some(offsets) = self.source_index.get(&source_id) {
+				let changed = mask_entries(
+					&mut self.store,
+					offsets,
+					crate::model_internal::VISIBLE_OFF_SOURCE,
+					!visible,
+				);
+				first_changed = min_offset(first_changed, changed);
+			}
+		}
+		self.relink(first_changed, anchor);
+	}
+
+	//Shows only entries containing `text`. Matches of earlier searches are cached while they
+	//narrow each other (e.g. while typing), so only their matches need to be searched again.
+	//Large searches run in the background (see `poll_search`); returns whether one was started.
+	pub fn start_search(&mut self, text: &str) -> bool {
+		self.search_job = None; //Cancels a stale search
+		self.messages.check_file();
+		while let Some((cached_text, _)) = self.search_cache.last() {
+			if text.contains(cached_text.as_str()) {
+				break;
+			}
+			self.search_cache.pop();
+		}
+
+		//Entries that are no candidates are hidden already, as they did not match a cached search
+		let candidates: Vec<u32> = match self.search_cache.last() {
+			Some((_, candidates)) => candidates.clone(),
+			None => (0..self.store.len() as u32).collect(),
+		};
+		if text.is_empty() || candidates.len() < search::BACKGROUND_MIN_ENTRIES {
+			let matches = candidates
+				.iter()
+				.copied()
+				.filter(|offset| self.message(*offset as usize).contains(text))
+				.collect();
+			self.apply_search(text, &candidates, matches);
+			false
+		} else {
+			let message_ids = candidates
+				.iter()
+				.map(|offset| self.store[*offset as usize].message)
+				.collect();
+			self.search_job = Some(search::SearchJob::start(
+				text,
+				candidates,
+				self.messages.clone(),
+				message_ids,
+			));
+			true
+		}
+	}
+
+	//Applies the background search once it is done; returns whether none is running anymore
+	pub fn poll_search(&mut self) -> bool {
+		let matches = match &mut self.search_job {
+			Some(job) => match job.poll() {
+				Some(matches) => matches,
+				None => return false,
+			},
+			None => return true,
+		};
+		if let Some(job) = self.search_job.take() {
+			self.apply_search(&job.text, &job.candidates, matches);
+		}
+		true
+	}
+
+	//All at once, so the view never shows a partial search
+	fn apply_search(&mut self, text: &str, candidates: &[u32], matches: Vec<u32>) {
+		let anchor = self.anchor_position();
+		let mut first_changed = None;
+		let mut next_match = matches.iter().copied().peekable();
+		for offset in candidates.iter().copied() {
+			let entry = &mut self.store[offset as usize];
+			let was_visible = entry.is_visible();
+			if next_match.next_if_eq(&offset).is_some() {
+				entry.visible &= !crate::model_internal::VISIBLE_OFF_FILTER;
+			} else {
+				entry.visible |= crate::model_internal::VISIBLE_OFF_FILTER;
+			}
+			if was_visible != entry.is_visible() && first_changed.is_none() {
+				first_changed = Some(offset as usize);
+			}
+		}
+		if text.is_empty() {
+			self.search_cache.clear(); //Everything matches, not worth caching
+		} else {
+			self.search_cache.push((text.to_string(), matches));
+		}
+		if self.search_text.is_empty() != text.is_empty() {
+			first_changed = Some(0); //Search hits of the density overview change everywhere
+		}
+		self.search_text = text.to_string();
+		self.relink(first_changed, anchor);
+	}
+
+	//If the store is sorted by timestamp, the ranges are found by binary search and only entries
+	//that enter or leave the range are touched. Otherwise, every entry has to be tested.
+	pub fn set_time_range(&mut self, time_range: Option<(DateTime<Utc>, DateTime<Utc>)>) {
+		if !self.sorted_by_timestamp() {
+			self.mask_time_range(time_range);
+			return;
+		}
+		let range = |time_range: Option<(DateTime<Utc>, DateTime<Utc>)>| match time_range {
+			Some((start, end)) => time_range_offsets(&self.store, start, end),
+			None => 0..self.store.len(),
+		};
+		let old_range = range(self.time_range);
+		let new_range = range(time_range);
+		let anchor = self.anchor_position();
+		let mut first_changed = None;
+		//Symmetric difference of the two ranges
+		let leaving = (old_range.start..std::cmp::min(old_range.end, new_range.start))
+			.chain(std::cmp::max(old_range.start, new_range.end)..old_range.end);
+		let entering = (new_range.start..std::cmp::min(new_range.end, old_range.start))
+			.chain(std::cmp::max(new_range.start, old_range.end)..new_range.end);
+		for offset in leaving.chain(entering) {
+			let entry = &mut self.store[offset];
+			let was_visible = entry.is_visible();
+			if new_range.contains(&offset) {
+				entry.visible &= !crate::model_internal::VISIBLE_OFF_TIME;
+			} else {
+				entry.visible |= crate::model_internal::VISIBLE_OFF_TIME;
+			}
+			if was_visible != entry.is_visible() {
+				first_changed = min_offset(first_changed, Some(offset));
+			}
+		}
+		self.time_range = time_range;
+		self.relink(first_changed, anchor);
+	}
+
+	//Tests every entry against the time range, see `set_time_range`
+	fn mask_time_range(&mut self, time_range: Option<(DateTime<Utc>, DateTime<Utc>)>) {
+		let range = time_range
+			.map(|(start, end)| Timestamp::from_datetime(&start)..=Timestamp::from_datetime(&end));
+		let anchor = self.anchor_position();
+		let mut first_changed = None;
+		for (offset, entry) in self.store.iter_mut().enumerate() {
+			let was_visible = entry.is_visible();
+			if range
+				.as_ref()
+				.is_none_or(|range| range.contains(&entry.timestamp))
+			{
+				entry.visible &= !crate::model_internal::VISIBLE_OFF_TIME;
+			} else {
+				entry.visible |= crate::model_internal::VISIBLE_OFF_TIME;
+			}
+			if was_visible != entry.is_visible() && first_changed.is_none() {
+				first_changed = Some(offset);
+			}
+		}
+		self.time_range = time_range;
+		self.relink(first_changed, anchor);
+	}
+
+	//Generic filter, which has to test every entry; prefer the incremental setters above
 	//pub fn filter_store(&mut self, filter : |&LogEntryExt| -> bool, active: bool) {
 	pub fn filter_store(&mut self, filter: &dyn Fn(&LogEntryExt) -> bool, active: bool, mask: u8) {
-		//Note: The code in this function must be fast. It is critical GUI code.
-		//If this code is slow, then the user will have noticeable GUI lag.
-
-		let (tmp_anchor_offset, rel_offset) = {
-			if let Some(anchor_offset) = self.anchor_offset {
-				if let Some(rel_offset) = self.abs_to_rel_offset(anchor_offset) {
-					(anchor_offset, rel_offset)
+		let anchor = self.anchor_position();
+		let mut first_changed = None;
+		for (offset, entry) in self.store.iter_mut().enumerate() {
+			if filter(entry) {
+				let was_visible = entry.is_visible();
+				if active {
+					entry.visible &= !mask; //remove mask to show entry
 				} else {
-					//Anchor is not active in viewport
-					//Test if anchor is between the lines of viewport
-					let mut rel_offset = None;
-					if let Some(mut abs_offset) = self.rel_to_abs_offset(0) {
-						let mut prev_offset = abs_offset;
-						for i in 1..self.visible_lines {
-							//Note: The last element points to itself, so it's safe in all cases
-							abs_offset = self.store[abs_offset].next_offset as usize;
-
-							if anchor_offset >= prev_offset && anchor_offset <= abs_offset {
-								rel_offset = Some(i);
-								break;
-							}
-							prev_offset = abs_offset;
-						}
-					}
-					if let Some(rel_offset) = rel_offset {
-						//Hold anchor in place because anchor is between the lines of viewport
-						(anchor_offset, rel_offset)
-					} else {
-						//Fallback: Align to the middle of the screen
-						(
-							anchor_offset,
-							(std::cmp::max(1, self.visible_lines) - 1) / 2,
-						)
-					}
+					entry.visible |= mask; //apply mask
+				}
+				if was_visible != entry.is_visible() && first_changed.is_none() {
+					first_changed = Some(offset);
 				}
-			} else if self.entry_count >= self.visible_lines {
+			}
+		}
+		self.relink(first_changed, anchor);
+	}
+
+	//Anchor and its row in the viewport, to keep it in place while the visible entries change
+	fn anchor_position(&self) -> (usize, usize) {
+		if let Some(anchor_offset) = self.anchor_offset {
+			if let Some(rel_offset) = self.abs_to_rel_offset(anchor_offset) {
+				(anchor_offset, rel_offset)
+			} else {
+				//Anchor is not active in viewport
+				//Test if anchor is between the lines of viewport
+				let mut rel_offset = None;
 				if let Some(mut abs_offset) = self.rel_to_abs_offset(0) {
-					//Got top visible line; now advance anchor by half the visible lines
-					let rel_offset = (std::cmp::max(1, self.visible_lines) - 1) / 2;
-					for _ in 0..rel_offset {
+					let mut prev_offset = abs_offset;
+					for i in 1..self.visible_lines {
 						//Note: The last element points to itself, so it's safe in all cases
 						abs_offset = self.store[abs_offset].next_offset as usize;
+
+						if anchor_offset >= prev_offset && anchor_offset <= abs_offset {
+							rel_offset = Some(i);
+							break;
+						}
+						prev_offset = abs_offset;
 					}
-					(abs_offset, rel_offset)
+				}
+				if let Some(rel_offset) = rel_offset {
+					//Hold anchor in place because anchor is between the lines of viewport
+					(anchor_offset, rel_offset)
 				} else {
-					(0, 0)
+					//Fallback: Align to the middle of the screen
+					(
+						anchor_offset,
+						(std::cmp::max(1, self.visible_lines) - 1) / 2,
+					)
+				}
+			}
+		} else if self.entry_count >= self.visible_lines {
+			if let Some(mut abs_offset) = self.rel_to_abs_offset(0) {
+				//Got top visible line; now advance anchor by half the visible lines
+				let rel_offset = (std::cmp::max(1, self.visible_lines) - 1) / 2;
+				for _ in 0..rel_offset {
+					//Note: The last element points to itself, so it's safe in all cases
+					abs_offset = self.store[abs_offset].next_offset as usize;
 				}
+				(abs_offset, rel_offset)
 			} else {
-				//No anchor; less elements in store than viewport size; just reset to zero
 				(0, 0)
 			}
+		} else {
+			//No anchor; less elements in store than viewport size; just reset to zero
+			(0, 0)
+		}
+	}
+
+	//Relinks the visible entries from the first one whose visibility changed; links, ids and
+	//density of the entries before it are still valid. Then restores the anchor position.
+	fn relink(
+		&mut self,
+		first_changed: Option<usize>,
+		(tmp_anchor_offset, rel_offset): (usize, usize),
+	) {
+		//Note: The code in this function must be fast. It is critical GUI code.
+		//If this code is slow, then the user will have noticeable GUI lag.
+		let from = match first_changed {
+			Some(from) => from,
+			None => return, //Visibility did not change
 		};
 
-		let mut next_entry_id = 0;
+		//Last visible entry before the change; usually close by
+		let prev = (0..from)
+			.rev()
+			.find(|offset| self.store[*offset].is_visible());
+		let mut next_entry_id = prev.map_or(0, |offset| self.store[offset].entry_id + 1);
 
-		let mut dummy = LogEntryExt {
-			timestamp: DateTime::<Utc>::from_utc(
-				NaiveDateTime::from_timestamp_opt(0, 0).unwrap(),
-				Utc,
-			),
-			severity: model::LogLevel::Error,
-			message: "Foo".to_string(),
-			source_id: 0,
-			visible: crate::model_internal::VISIBLE_ON,
-			entry_id: 0,
-			prev_offset: 0,
-			next_offset: 0,
-		};
+		//Keep the density of earlier blocks, recount the block the change falls into
+		let block_size = self.density_block_size;
+		let mut density = std::mem::take(&mut self.density);
+		let first_block = next_entry_id as usize / block_size;
+		density.truncate(first_block);
+		density.resize(
+			self.store.len().div_ceil(block_size),
+			DensityBlock::default(),
+		);
+		if let Some(mut offset) = prev {
+			for _ in first_block * block_size..next_entry_id as usize {
+				count_density(
+					&mut density[first_block],
+					&self.store[offset],
+					&self.search_text,
+				);
+				offset = self.store[offset].prev_offset as usize;
+			}
+		}
 
+		self.visible_offsets.truncate(next_entry_id as usize);
 		{
-			let mut prev = &mut dummy;
-			let mut prev_offset = 0;
-			for (offset, entry) in self.store.iter_mut().enumerate() {
-				if filter(entry) {
-					if active {
-						entry.visible &= !mask; //remove mask to show entry
-					} else {
-						entry.visible |= mask; //apply mask
-					}
-				}
+			let (head, tail) = self.store.split_at_mut(from);
+			let mut prev_offset = prev.unwrap_or(0) as u32;
+			let mut prev_entry = prev.map(|offset| &mut head[offset]);
+			for (idx, entry) in tail.iter_mut().enumerate() {
 				if entry.is_visible() {
+					let offset = (from + idx) as u32;
 					entry.entry_id = next_entry_id;
+					self.visible_offsets.push(offset);
+					count_density(
+						&mut density[next_entry_id as usize / block_size],
+						entry,
+						&self.search_text,
+					);
 					next_entry_id += 1;
 
-					prev.next_offset = offset as u32;
-					entry.prev_offset = prev_offset;
-
-					prev_offset = offset as u32;
-					prev = entry;
+					match prev_entry {
+						Some(prev) => {
+							prev.next_offset = offset;
+							entry.prev_offset = prev_offset;
+						}
+						None => {
+							self.first_offset = offset as usize;
+							entry.prev_offset = offset; //First element points to itself
+						}
+					}
+					prev_offset = offset;
+					prev_entry = Some(entry);
 				}
 			}
 
-			//Prev is the last element now:
-			self.last_offset = prev_offset as usize;
-			prev.next_offset = prev_offset; //Last element points to itself
+			match prev_entry {
+				Some(last) => {
+					last.next_offset = prev_offset; //Last element points to itself
+					self.last_offset = prev_offset as usize;
+				}
+				None => {
+					self.first_offset = 0;
+					self.last_offset = 0;
+				}
+			}
 		}
 
+		self.timeline = None; //rebuilt on the next draw of the timeline
 		self.entry_count = next_entry_id as usize; //Conveniently, we can use this as number of elements
-		self.first_offset = dummy.next_offset as usize; //The element after the dummy is the first real element
-		if !self.store.is_empty() {
-			self.store[self.first_offset].prev_offset = self.first_offset as u32;
-			//First element points to itself
-		}
+		density.truncate(self.entry_count.div_ceil(block_size));
+		self.density = density;
 
+		self.viewport_row = 0;
 		if !self.store.is_empty() && self.store[tmp_anchor_offset].is_visible() {
 			self.viewport_offset = tmp_anchor_offset;
 			self.scroll(-(rel_offset as i64), self.visible_lines);
 		} else {
-			let mut found = false;
-			for (offset, _) in self
-				.store
-				.iter()
-				.enumerate() //offset in vector
-				.skip(tmp_anchor_offset)
-				.filter(|(_, x)| x.is_visible())
-				.take(1)
-			{
+			let substitute = self.visible_from(tmp_anchor_offset).next();
+			if let Some(offset) = substitute {
 				self.viewport_offset = offset;
-				found = true;
-			}
-			if !found {
+			} else {
 				self.viewport_offset = self.last_offset;
 				log::info!(
 					"Anchor not visible, substitute anchor with offset {} not found",
@@ -230,6 +1082,48 @@ impl LogStoreLinear {
 		}
 	}
 
+	//Moves the viewport so that the given entry is centered, if possible
+	pub fn jump_to(&mut self, abs_offset: usize) {
+		if abs_offset >= self.store.len() || !self.store[abs_offset].is_visible() {
+			return;
+		}
+		self.viewport_offset = abs_offset;
+		self.viewport_row = 0;
+		let rel_offset = (std::cmp::max(1, self.visible_lines) - 1) / 2;
+		self.scroll(-(rel_offset as i64), self.visible_lines);
+		self.clamp_viewport_bottom(self.visible_lines);
+	}
+
+	//Scroll the message column horizontally, returns true if the offset changed
+	pub fn scroll_horz(&mut self, dx: f64) -> bool {
+		let max_offset = f64::max(0.0, self.content_width - self.h_scroll_bar.bar_width);
+		let h_offset_old = self.h_offset;
+		self.h_offset = (self.h_offset + dx).clamp(0.0, max_offset);
+		h_offset_old != self.h_offset
+	}
+
+	//Rows of wrapped or expanded entries make it possible to jump past the last full screen; scroll back up in that case
+	pub fn clamp_viewport_bottom(&mut self, window_size: usize) {
+		if !self.rows_vary() {
+			return; //Scrolling by entries takes care of this on its own
+		}
+		while self.rows_from_viewport(window_size) < window_size {
+			if !self.scroll(-1, window_size) {
+				break; //reached start of list
+			}
+		}
+	}
+
+	//Number of entries the scroll bar maps onto
+	fn scroll_range(&self, window_size: usize) -> usize {
+		if !self.rows_vary() {
+			self.entry_count.saturating_sub(window_size)
+		} else {
+			//The number of entries on the last screen depends on their rows
+			self.entry_count.saturating_sub(1)
+		}
+	}
+
 	pub fn percentage_to_offset(&self, perc: f64, window_size: usize) -> Option<usize> {
 		if !(0.0..=1.0).contains(&perc) {
 			return None;
@@ -240,31 +1134,32 @@ impl LogStoreLinear {
 		if self.entry_count == 0 {
 			return None;
 		}
-		if window_size >= self.entry_count {
+		if self.scroll_range(window_size) == 0 {
 			return Some(0);
 		}
 
-		let entry_id = ((self.entry_count - window_size) as f64 * perc).round() as u32;
+		let entry_id = (self.scroll_range(window_size) as f64 * perc).round() as usize;
+		self.entry_id_to_offset(entry_id)
+	}
 
-		self.store
-			.iter()
-			.enumerate()
-			.skip(std::cmp::max(1, entry_id as usize) - 1)
-			.filter(|(_, x)| x.is_visible() && x.entry_id == entry_id)
-			.take(1)
-			.next()
-			.map_or_else(|| unreachable!(), |(offset, _)| Some(offset))
+	pub fn entry_id_to_offset(&self, entry_id: usize) -> Option<usize> {
+		self.visible_offsets
+			.get(entry_id)
+			.map(|offset| *offset as usize)
 	}
 
 	pub fn get_scroll_percentage(&self, window_size: usize) -> f64 {
 		if self.entry_count == 0 {
 			return 0.0; //Early exit to prevent getting nonexistent vec elements!
 		}
-		if self.entry_count <= window_size {
+		if self.scroll_range(window_size) == 0 {
 			return 0.0; //Early exit to prevent division by 0, negative percentage
 		}
-		let percentage = (self.store[self.viewport_offset].entry_id as f64)
-			/ ((self.entry_count - window_size) as f64);
+		//Interpolate within a wrapped entry that is partially scrolled out of view
+		let row_fraction = self.first_row(self.viewport_offset) as f64
+			/ self.entry_rows(self.viewport_offset) as f64;
+		let percentage = (self.store[self.viewport_offset].entry_id as f64 + row_fraction)
+			/ (self.scroll_range(window_size) as f64);
 		if percentage > 1.0 {
 			return 1.0; //clamp down if scrolled too far or window too large
 		}
@@ -277,37 +1172,298 @@ impl LogStoreLinear {
 			return false; //Early exit to prevent getting nonexistent vec elements!
 		}
 		let viewport_offset_old = self.viewport_offset;
+		let viewport_row_old = self.viewport_row;
+		if !self.rows_vary() {
+			//One row per entry: Jump by entry id
+			let entry_id = self.entry_id_at(self.viewport_offset);
+			let new_id = if lines < 0 {
+				entry_id.saturating_sub(lines.unsigned_abs() as usize)
+			} else {
+				let last_id = self.entry_count.saturating_sub(window_size);
+				if entry_id >= last_id {
+					entry_id //bottomed out window, or window larger than number of rows
+				} else {
+					std::cmp::min(entry_id + lines as usize, last_id)
+				}
+			};
+			self.viewport_offset =
+				self.visible_offsets[std::cmp::min(new_id, self.entry_count - 1)] as usize;
+			self.viewport_row = 0;
+			return viewport_offset_old != self.viewport_offset
+				|| viewport_row_old != self.viewport_row;
+		}
+		self.viewport_row = self.first_row(self.viewport_offset);
 		let mut abs_lines = lines.abs();
 		if lines < 0 {
 			while abs_lines > 0 {
+				if self.viewport_row > 0 {
+					//Scroll within a wrapped entry
+					self.viewport_row -= 1;
+					abs_lines -= 1;
+					continue;
+				}
 				let new_offset = self.store[self.viewport_offset].prev_offset as usize;
 				if self.viewport_offset == new_offset {
 					break; //reached end of list
 				}
 				self.viewport_offset = new_offset;
+				self.viewport_row = self.entry_rows(new_offset) - 1;
 				abs_lines -= 1;
 			}
 		} else {
-			if self.entry_count <= window_size {
-				return false; //no scrolling down if window larger than number of rows
-			}
 			while abs_lines > 0 {
-				if (self.entry_count - window_size)
-					<= self.store[self.viewport_offset].entry_id as usize
-				{
+				if self.rows_from_viewport(window_size + 1) <= window_size {
 					break; //stop scrolling down, bottomed out window
 				}
 
+				if self.viewport_row + 1 < self.entry_rows(self.viewport_offset) {
+					//Scroll within a wrapped entry
+					self.viewport_row += 1;
+					abs_lines -= 1;
+					continue;
+				}
+
 				let new_offset = self.store[self.viewport_offset].next_offset as usize;
 				if self.viewport_offset == new_offset {
 					break; //reached end of list
 				}
 
 				self.viewport_offset = new_offset;
+				self.viewport_row = 0;
 				abs_lines -= 1;
 			}
 		}
 
-		viewport_offset_old != self.viewport_offset
+		viewport_offset_old != self.viewport_offset || viewport_row_old != self.viewport_row
+	}
+}
+
+//Sets or clears `mask` of the entries at `offsets`, returns the first offset whose visibility changed
+fn mask_entries(store: &mut [LogEntryExt], offsets: &[u32], mask: u8, hide: bool) -> Option<usize> {
+	let mut first_changed = None;
+	for offset in offsets {
+		let entry = &mut store[*offset as usize];
+		let was_visible = entry.is_visible();
+		if hide {
+			entry.visible |= mask;
+		} else {
+			entry.visible &= !mask;
+		}
+		if was_visible != entry.is_visible() && first_changed.is_none() {
+			first_changed = Some(*offset as usize); //offsets are ascending
+		}
+	}
+	first_changed
+}
+
+fn min_offset(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+	match (a, b) {
+		(Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+		_ => a.or(b),
+	}
+}
+
+//Visible entries are search hits while searching, as the search hides all others
+fn count_density(block: &mut DensityBlock, entry: &LogEntryExt, search_text: &str) {
+	match entry.severity {
+		model::LogLevel::Critical => block.critical += 1,
+		model::LogLevel::Error => block.error += 1,
+		model::LogLevel::Warning => block.warning += 1,
+		_ => {}
+	}
+	if !search_text.is_empty() {
+		block.search_hits += 1;
+	}
+}
+
+//Entries of all sources ordered by timestamp, then by source (in the order given), then by
+//their order in the source. Entries of a source are usually in order already, so they are
+//merged instead of sorted. Sources that are not in order are sorted first (stable).
+//Also returns the position of each entry in the sources, see `LogStoreLinear::file_positions`.
+pub fn merge_sources(mut sources: Vec<Vec<LogEntryExt>>) -> (Vec<LogEntryExt>, Vec<u32>) {
+	let mut first_positions = Vec::with_capacity(sources.len());
+	let mut source_positions = Vec::with_capacity(sources.len()); //only if the source was sorted
+	let mut total = 0;
+	for (rank, entries) in sources.iter_mut().enumerate() {
+		first_positions.push(total);
+		total += entries.len() as u32;
+		if entries.is_sorted_by_key(|entry| entry.timestamp) {
+			source_positions.push(None);
+		} else {
+			log::info!("Sorting entries of source {} by timestamp", rank);
+			let mut order: Vec<u32> = (0..entries.len() as u32).collect();
+			order.sort_by_key(|idx| entries[*idx as usize].timestamp);
+			source_positions.push(Some(order.clone()));
+			permute(entries, &mut order);
+		}
+	}
+	let mut merged = Vec::with_capacity(total as usize);
+	let mut file_positions = Vec::with_capacity(total as usize);
+	let mut merged_counts = vec![0; sources.len()];
+	let mut sources: Vec<std::vec::IntoIter<LogEntryExt>> = sources
+		.into_iter()
+		.map(|entries| entries.into_iter())
+		.collect();
+	//Next entry of each source, smallest (timestamp, rank) first
+	let next = |source: &std::vec::IntoIter<LogEntryExt>, rank: usize| {
+		source
+			.as_slice()
+			.first()
+			.map(|entry| std::cmp::Reverse((entry.timestamp, rank)))
+	};
+	let mut heap: BinaryHeap<_> = sources
+		.iter()
+		.enumerate()
+		.filter_map(|(rank, source)| next(source, rank))
+		.collect();
+	while let Some(std::cmp::Reverse((_, rank))) = heap.pop() {
+		merged.extend(sources[rank].next());
+		let idx = merged_counts[rank];
+		merged_counts[rank] += 1;
+		let idx = source_positions[rank]
+			.as_ref()
+			.map_or(idx as u32, |positions: &Vec<u32>| positions[idx]);
+		file_positions.push(first_positions[rank] + idx);
+		heap.extend(next(&sources[rank], rank));
+	}
+	(merged, file_positions)
+}
+
+//Reorders `items` so that the item at `order[idx]` moves to `idx`, without copying them.
+//`order` must be a permutation and is used up.
+fn permute<T>(items: &mut [T], order: &mut [u32]) {
+	for start in 0..order.len() {
+		//Follow the cycle, marking the places done by pointing them to themselves
+		let mut idx = start;
+		loop {
+			let from = order[idx] as usize;
+			order[idx] = idx as u32;
+			if from == start {
+				break;
+			}
+			items.swap(idx, from);
+			idx = from;
+		}
+	}
+}
+
+//Offsets of the entries within `start` and `end` (inclusive) of a store sorted by timestamp
+pub fn time_range_offsets(
+	store: &[LogEntryExt],
+	start: DateTime<Utc>,
+	end: DateTime<Utc>,
+) -> std::ops::Range<usize> {
+	let (start, end) = (
+		Timestamp::from_datetime(&start),
+		Timestamp::from_datetime(&end),
+	);
+	let first = store.partition_point(|entry| entry.timestamp < start);
+	let last = store.partition_point(|entry| entry.timestamp <= end);
+	first..std::cmp::max(first, last)
+}
+
+//Row `row` of a message that is soft-wrapped every `wrap_chars` characters
+pub fn wrapped_row(message: &str, wrap_chars: usize, row: usize) -> &str {
+	if wrap_chars == 0 {
+		return if row == 0 { message } else { "" };
+	}
+	let mut char_indices = message.char_indices().map(|(idx, _)| idx);
+	let start = char_indices.nth(row * wrap_chars).unwrap_or(message.len());
+	let end = message[start..]
+		.char_indices()
+		.nth(wrap_chars)
+		.map_or(message.len(), |(idx, _)| start + idx);
+	&message[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_wrapped_row() {
+		assert_eq!(wrapped_row("abcdefg", 0, 0), "abcdefg");
+		assert_eq!(wrapped_row("abcdefg", 0, 1), "");
+		assert_eq!(wrapped_row("abcdefg", 3, 0), "abc");
+		assert_eq!(wrapped_row("abcdefg", 3, 2), "g");
+		assert_eq!(wrapped_row("abcdefg", 3, 3), "");
+		assert_eq!(wrapped_row("äöüßé", 2, 1), "üß");
+	}
+
+	fn at(secs: i64) -> DateTime<Utc> {
+		Utc.timestamp_opt(secs, 0).unwrap()
+	}
+
+	//The message id tells entries apart
+	fn entry(secs: i64, source_id: u32, message: u32) -> LogEntryExt {
+		LogEntryExt {
+			timestamp: Timestamp::from_datetime(&at(secs)),
+			severity: model::LogLevel::Info,
+			message,
+			source_id,
+			custom_fields: Box::new([]),
+			visible: crate::model_internal::VISIBLE_ON,
+			entry_id: 0,
+			prev_offset: 0,
+			next_offset: 0,
+		}
+	}
+
+	#[test]
+	fn test_merge_sources() {
+		let (merged, file_positions) = merge_sources(vec![
+			vec![
+				entry(10, 1, 0),
+				entry(20, 1, 1),
+				entry(20, 1, 2),
+				entry(30, 1, 3),
+			],
+			vec![],
+			vec![
+				entry(5, 3, 4),
+				entry(20, 3, 5),
+				entry(20, 3, 6),
+				entry(40, 3, 7),
+			],
+			//Not in order, equal timestamps keep their order
+			vec![entry(20, 4, 8), entry(10, 4, 9), entry(20, 4, 10)],
+		]);
+		let order: Vec<(u32, u32)> = merged
+			.iter()
+			.map(|entry| (entry.source_id, entry.message))
+			.collect();
+		assert_eq!(
+			order,
+			vec![
+				(3, 4),
+				(1, 0),
+				(4, 9),
+				(1, 1),
+				(1, 2),
+				(3, 5),
+				(3, 6),
+				(4, 8),
+				(4, 10),
+				(1, 3),
+				(3, 7),
+			]
+		);
+		//Messages are numbered in file order
+		let messages: Vec<u32> = merged.iter().map(|entry| entry.message).collect();
+		assert_eq!(file_positions, messages);
+		assert!(merge_sources(vec![]).0.is_empty());
+	}
+
+	#[test]
+	fn test_time_range_offsets() {
+		let store: Vec<LogEntryExt> = [10, 20, 20, 30, 40]
+			.iter()
+			.map(|secs| entry(*secs, 0, 0))
+			.collect();
+		assert_eq!(time_range_offsets(&store, at(20), at(30)), 1..4);
+		assert_eq!(time_range_offsets(&store, at(15), at(25)), 1..3);
+		assert_eq!(time_range_offsets(&store, at(0), at(100)), 0..5);
+		assert_eq!(time_range_offsets(&store, at(41), at(100)), 5..5);
+		assert_eq!(time_range_offsets(&store, at(30), at(20)), 3..3);
 	}
 }
@@ -9,6 +9,7 @@ use gtk::cairo;
 use gtk::gdk;
 use gtk::gio;
 use gtk::glib;
+use gtk::pango;
 
 use gdk::EventMask;
 use gio::prelude::*;
@@ -23,12 +24,23 @@ use std::rc::Rc;
 #[allow(unused_imports)]
 use regex::Regex;
 
+mod columns;
+mod copy_format;
+mod correction;
 mod log_store;
+mod message_arena;
 mod model;
 mod model_internal;
 mod parse;
+mod passwords;
+mod search;
+mod session;
+mod settings;
+mod theme;
+mod timeline;
 
 use log_store::LogStoreLinear;
+use log_store::ScrollBarHorz;
 use log_store::ScrollBarVert;
 
 use model_internal::LogEntryExt;
@@ -49,6 +61,7 @@ enum LogSourcesColumns {
 	Text = 2,
 	Id = 3,
 	ChildCount = 4,
+	Color = 5,
 }
 
 fn toggle_row(
@@ -220,11 +233,7 @@ fn toggle_row(
 	*/
 
 	let now = Instant::now();
-	store.filter_store(
-		&|entry: &LogEntryExt| entry.source_id >= first_id && entry.source_id <= last_id,
-		active,
-		crate::model_internal::VISIBLE_OFF_SOURCE,
-	);
+	store.set_sources_visible(first_id, last_id, active);
 	let elapsed = now.elapsed();
 	log::info!(
 		"Time to update store: {}ms",
@@ -239,20 +248,48 @@ fn toggle_row(
 //--------------------------------------------------------------------------------------------------
 //--------------------------------------------------------------------------------------------------
 
+//Draw a single line of text with its top left corner at (x, y), returns its width
+fn draw_text(
+	ctx: &cairo::Context,
+	style_ctx: &gtk::StyleContext,
+	layout: &pango::Layout,
+	(x, y): (f64, f64),
+	text: &str,
+	color: (f64, f64, f64),
+) -> f64 {
+	layout.set_text(text);
+	let attrs = pango::AttrList::new();
+	attrs.insert(pango::AttrColor::new_foreground(
+		(color.0 * 65535.0) as u16,
+		(color.1 * 65535.0) as u16,
+		(color.2 * 65535.0) as u16,
+	));
+	layout.set_attributes(Some(&attrs));
+	gtk::render_layout(style_ctx, ctx, x, y, layout);
+	layout.pixel_size().0 as f64
+}
+
+fn text_width(layout: &pango::Layout, text: &str) -> f64 {
+	layout.set_text(text);
+	layout.pixel_size().0 as f64
+}
+
 fn draw(
 	store: &mut LogStoreLinear,
 	drawing_area: &DrawingArea,
 	ctx: &cairo::Context,
-	font: &str,
 ) -> gtk::Inhibit {
 	//store.store.push(model::LogEntry { message: "TestTrace 309468456".to_string(),       severity: model::LogLevel::Trace,    ..Default::default() });
 	//log::info!("{}", store.store.len());
 
 	//log::info!("w: {} h: {}", drawing_area.allocated_width(), drawing_area.allocated_height());
 
-	ctx.set_source_rgb(1.0, 1.0, 1.0);
+	let background = store.theme.background;
+	ctx.set_source_rgb(background.0, background.1, background.2);
 	ctx.paint().unwrap();
 
+	store.messages.check_file(); //Memory-mapped messages must not be read past the file end
+
 	/*ctx.set_source_rgb(1.0, 0.0, 0.0);
 	ctx.rectangle(10.0, 10.0, 2.0, 2.0);
 	ctx.fill().unwrap();
@@ -271,37 +308,135 @@ fn draw(
 
 	ctx.set_source_rgb(0.0, 0.0, 0.0);
 
-	store.line_spacing = f64::max(store.line_spacing, store.font_size + 2.0); //prevent overlapping lines with large font
-	store.visible_lines = (f64::max(0.0, (h as f64) - store.border_top - store.border_bottom)
-		/ store.line_spacing) as usize;
+	//Pango picks fallback fonts for glyphs missing in the selected font (CJK, emoji)
+	let style_ctx = drawing_area.style_context();
+	let layout = drawing_area.create_pango_layout(None);
+	layout.set_font_description(Some(&store.font));
+	layout.set_single_paragraph_mode(true);
+	let metrics = drawing_area
+		.pango_context()
+		.metrics(Some(&store.font), None);
+	let font_height = (metrics.ascent() + metrics.descent()) as f64 / pango::SCALE as f64;
+	store.line_spacing = f64::ceil(font_height + 2.0 * store.row_padding);
+	store.header_height = store.line_spacing;
+	//Without wrapping, reserve space for the horizontal scroll bar at the bottom
+	let h_scroll_bar_space = if store.wrap {
+		0.0
+	} else {
+		store.h_scroll_bar.bar_height + store.h_scroll_bar.bar_padding
+	};
+	store.visible_lines = (f64::max(
+		0.0,
+		(h as f64)
+			- store.border_top
+			- store.header_height
+			- store.border_bottom
+			- h_scroll_bar_space,
+	) / store.line_spacing) as usize;
+
+	let timestamp_sample = settings::timestamp_sample(&store.timestamp_format, &store.time_zone);
+	for column in store.columns.iter_mut() {
+		let sample_text = if column.kind == columns::ColumnKind::Timestamp {
+			&timestamp_sample
+		} else {
+			column.sample_text()
+		};
+		column.auto_width = text_width(&layout, sample_text) + columns::COLUMN_PADDING;
+	}
+	let column_positions = store.column_positions();
+	let rows_top = store.border_top + store.header_height;
+	//The message column ends where the vertical scroll bar starts
+	let message_x = column_positions
+		.iter()
+		.find(|(_, _, width)| width.is_infinite())
+		.map_or(store.border_left, |(_, column_x, _)| *column_x);
+	let message_width = f64::max(
+		0.0,
+		w as f64 - store.scroll_bar.bar_width - store.scroll_bar.bar_padding * 2.0 - message_x,
+	);
+	store.wrap_chars = if store.wrap {
+		//Note: Exact for monospace fonts only, double-width glyphs (CJK) may be clipped
+		let char_width = text_width(&layout, "M");
+		std::cmp::max(1, (message_width / f64::max(1.0, char_width)) as usize)
+	} else {
+		0
+	};
+	//Layouts are anchored top left, center the text vertically in its row
+	let font_top_y = f64::max(0.0, store.line_spacing - font_height) / 2.0;
+
+	//-----------------------------------------------------------------------------
+	//Column header
+	//-----------------------------------------------------------------------------
+	let header_background = store.theme.header_background;
+	ctx.set_source_rgb(
+		header_background.0,
+		header_background.1,
+		header_background.2,
+	);
+	ctx.rectangle(0.0, store.border_top, w as f64, store.header_height);
+	ctx.fill().unwrap();
+	for (idx, column_x, width) in &column_positions {
+		draw_text(
+			ctx,
+			&style_ctx,
+			&layout,
+			(*column_x, store.border_top + font_top_y),
+			store.columns[*idx].title(),
+			store.theme.header_foreground,
+		);
+		if width.is_finite() {
+			let separator = store.theme.separator;
+			ctx.set_source_rgb(separator.0, separator.1, separator.2);
+			ctx.rectangle(
+				column_x + width - columns::COLUMN_PADDING / 2.0,
+				store.border_top,
+				1.0,
+				store.header_height,
+			);
+			ctx.fill().unwrap();
+		}
+	}
 
-	if store.store.len() < store.visible_lines {
+	if store.rows_vary() {
+		//Window or wrap width may have changed, do not leave empty rows at the bottom
+		store.clamp_viewport_bottom(store.visible_lines);
+	} else if store.store.len() < store.visible_lines {
 		//No scrolling possible, less entries than rows on GUI!
 		store.viewport_offset = 0;
+		store.viewport_row = 0;
 	} else if store.viewport_offset > store.store.len() - store.visible_lines {
 		store.viewport_offset = store.store.len() - store.visible_lines;
+		store.viewport_row = 0;
 	}
 
 	//-----------------------------------------------------------------------------
 	//Draw loop
 	//-----------------------------------------------------------------------------
 	let mut anchor_drawn = false;
-
-	for (i, (offset, entry)) in store
-		.store
-		.iter()
-		.enumerate() //offset in vector
-		.skip(store.viewport_offset)
-		.filter(|(_, x)| x.is_visible())
-		.take(store.visible_lines)
-		//index of filtered element:
-		.enumerate()
-	{
-		ctx.select_font_face(font, cairo::FontSlant::Normal, cairo::FontWeight::Normal);
-		ctx.set_font_size(store.font_size);
-
-		let mut draw_highlight = if Some(i) == store.hover_line {
-			ctx.set_source_rgb(0.8, 0.8, 0.8);
+	let hover_offset = store
+		.hover_line
+		.and_then(|line| store.rel_to_abs_offset(line));
+	let mut content_width: f64 = 0.0;
+	//Visual row the current entry starts in, relative to the viewport
+	let mut row = 0;
+
+	for offset in store.visible_from(store.viewport_offset) {
+		let entry = &store.store[offset];
+		if row >= store.visible_lines {
+			break;
+		}
+		let first_row = store.first_row(offset);
+		let entry_rows = store.entry_rows(offset) - first_row;
+		let rows_drawn = std::cmp::min(entry_rows, store.visible_lines - row);
+		let row_y = rows_top + store.line_spacing * row as f64;
+		let entry_height = store.line_spacing * rows_drawn as f64;
+
+		let severity_colors = *store.theme.severity(&entry.severity);
+		let severity_color = severity_colors.foreground;
+
+		let mut draw_highlight = if Some(offset) == hover_offset {
+			let hover = store.theme.hover;
+			ctx.set_source_rgb(hover.0, hover.1, hover.2);
 			true
 		} else {
 			false
@@ -313,131 +448,153 @@ fn draw(
 				&& store.selected_range.unwrap().1 >= offset))
 			&& !store.excluded_single.contains(&offset)
 		{
-			if draw_highlight {
-				//Cumulative: Row is selected and hovered over
-				ctx.set_source_rgb(0.7, 0.7, 1.0);
+			let selected = if draw_highlight {
+				store.theme.selected_hover
 			} else {
-				ctx.set_source_rgb(0.8, 0.8, 1.0);
-			}
+				store.theme.selected
+			};
+			ctx.set_source_rgb(selected.0, selected.1, selected.2);
 			draw_highlight = true;
 		}
 
 		if draw_highlight {
-			ctx.rectangle(
-				0.0,
-				store.border_top + store.line_spacing * i as f64,
-				w as f64,
-				store.line_spacing,
+			ctx.rectangle(0.0, row_y, w as f64, entry_height);
+			ctx.fill().unwrap();
+		} else if let Some(severity_background) = severity_colors.background {
+			ctx.set_source_rgb(
+				severity_background.0,
+				severity_background.1,
+				severity_background.2,
 			);
+			ctx.rectangle(0.0, row_y, w as f64, entry_height);
 			ctx.fill().unwrap();
 		}
 
-		match entry.severity {
-			model::LogLevel::Critical => {
-				ctx.set_source_rgb(0.5, 0.0, 0.0);
-			} // Dark red
-			model::LogLevel::Error => {
-				ctx.set_source_rgb(1.0, 0.0, 0.0);
-			} //Red
-			model::LogLevel::Warning => {
-				ctx.set_source_rgb(0.77, 0.58, 0.0);
-			} //Dirty yellow-orange
-			model::LogLevel::Info => {
-				ctx.set_source_rgb(0.0, 0.0, 0.0);
-			} //Black
-			model::LogLevel::Debug => {
-				ctx.set_source_rgb(0.6, 0.6, 0.6);
-			} //Grey
-			model::LogLevel::Trace => {
-				ctx.set_source_rgb(0.4, 0.4, 0.4);
-			} //Light grey
-		}
-
-		let offset_y = store.border_top
-			+ store.line_spacing * i as f64
-			+ f64::max(0.0, store.line_spacing - store.font_size) / 2.0;
-		//Anchor point of text is bottom left, excluding descent.
-		//We want to anchor on top left though, so calculate that away:
-		let font_offset_y = offset_y + store.font_size - ctx.font_extents().unwrap().descent();
-
-		let date_str = entry.timestamp.format("%d.%m.%y %T%.3f").to_string();
-		ctx.move_to(store.border_left, font_offset_y);
-		ctx.show_text(&date_str).unwrap();
-
-		let short_sev = match entry.severity {
-			model::LogLevel::Critical => "CRI",
-			model::LogLevel::Error => "ERR",
-			model::LogLevel::Warning => "WRN",
-			model::LogLevel::Info => "INF",
-			model::LogLevel::Debug => "DBG",
-			model::LogLevel::Trace => "TRC",
-		};
+		if store.bookmarks.contains(&offset) {
+			let bookmark = store.theme.bookmark;
+			ctx.set_source_rgb(bookmark.0, bookmark.1, bookmark.2);
+			ctx.rectangle(2.0, row_y, 6.0, entry_height);
+			ctx.fill().unwrap();
+		}
+
+		let source_color = store.source_color(entry.source_id);
+		ctx.set_source_rgb(source_color.0, source_color.1, source_color.2);
+		ctx.rectangle(store.border_left - 6.0, row_y, 4.0, entry_height);
+		ctx.fill().unwrap();
 
-		ctx.move_to(store.border_left + 180.0, font_offset_y);
-		ctx.show_text(&short_sev).unwrap();
+		let font_offset_y = row_y + font_top_y;
 
 		if let Some(anchor_offset) = store.anchor_offset {
-			if offset == anchor_offset {
-				ctx.move_to(store.border_left - 20.0, font_offset_y);
-				ctx.show_text(&"→").unwrap(); //TODO: Replace with anchor symbol
-				anchor_drawn = true;
+			let anchor_y = if offset == anchor_offset {
+				Some(font_offset_y)
 			} else if !anchor_drawn {
 				if offset >= anchor_offset {
-					ctx.move_to(
-						store.border_left - 20.0,
-						font_offset_y - store.line_spacing / 2.0,
-					);
-					ctx.show_text(&"→").unwrap(); //TODO: Replace with anchor symbol
-					anchor_drawn = true;
-				} else if i == store.visible_lines - 1 || offset == store.last_offset {
-					ctx.move_to(
-						store.border_left - 20.0,
-						font_offset_y + store.line_spacing / 2.0,
+					Some(font_offset_y - store.line_spacing / 2.0)
+				} else if row + rows_drawn >= store.visible_lines || offset == store.last_offset {
+					Some(font_offset_y + store.line_spacing / 2.0)
+				} else {
+					None
+				}
+			} else {
+				None
+			};
+			if let Some(anchor_y) = anchor_y {
+				//TODO: Replace with anchor symbol
+				draw_text(
+					ctx,
+					&style_ctx,
+					&layout,
+					(store.border_left - 20.0, anchor_y),
+					"→",
+					severity_color,
+				);
+				anchor_drawn = true;
+			}
+		}
+
+		for (idx, column_x, width) in &column_positions {
+			let kind = &store.columns[*idx].kind;
+			if *kind == columns::ColumnKind::Message {
+				//Clip to the message column, which is scrolled horizontally or wrapped
+				ctx.save().unwrap();
+				ctx.rectangle(message_x, row_y, message_width, entry_height);
+				ctx.clip();
+				for r in 0..rows_drawn {
+					let text = columns::printable(store.entry_row(offset, first_row + r));
+					let text_y = font_offset_y + store.line_spacing * r as f64;
+					let mut row_width = draw_text(
+						ctx,
+						&style_ctx,
+						&layout,
+						(column_x - store.h_offset, text_y),
+						&text,
+						severity_color,
 					);
-					ctx.show_text(&"→").unwrap(); //TODO: Replace with anchor symbol
-					anchor_drawn = true;
+
+					let hidden_lines = store.hidden_lines(offset);
+					if hidden_lines > 0 && first_row + r + 1 == store.entry_rows(offset) {
+						//Collapsed multi-line entry, double click expands it
+						row_width += columns::COLUMN_PADDING;
+						row_width += draw_text(
+							ctx,
+							&style_ctx,
+							&layout,
+							(column_x - store.h_offset + row_width, text_y),
+							&format!("⏎ +{} lines", hidden_lines),
+							store.theme.indicator,
+						);
+					}
+					if store.wrap_chars == 0 {
+						content_width = f64::max(content_width, row_width);
+					}
 				}
+				ctx.restore().unwrap();
+				continue;
+			}
+			if width.is_finite() {
+				//Clip long cell contents so they do not run into the next column
+				ctx.save().unwrap();
+				ctx.rectangle(
+					*column_x,
+					row_y,
+					width - columns::COLUMN_PADDING,
+					store.line_spacing,
+				);
+				ctx.clip();
+			}
+			let cell_color = if *kind == columns::ColumnKind::Source {
+				source_color
+			} else {
+				severity_color
+			};
+			draw_text(
+				ctx,
+				&style_ctx,
+				&layout,
+				(*column_x, font_offset_y),
+				&columns::cell_text(kind, store, offset),
+				cell_color,
+			);
+			if width.is_finite() {
+				ctx.restore().unwrap();
 			}
 		}
 
-		ctx.move_to(store.border_left + 210.0, font_offset_y);
+		row += rows_drawn;
 
 		/*let font_face = ctx.get_font_face();
 		let new_font_face = cairo::FontFace::toy_create("cairo :monospace", font_face.toy_get_slant(), font_face.toy_get_weight());
 		ctx.set_font_face(&new_font_face);*/
-
-		ctx.show_text(&entry.message).unwrap();
-
-		/*if let Some(source_name) = store.log_sources.get(&entry.source_id) {
-			ctx.move_to(store.border_left + 210.0, font_offset_y + store.font_size);
-			ctx.set_font_size(f64::round(store.font_size * 0.7));
-			ctx.set_source_rgb(0.5, 0.5, 0.5);
-			ctx.show_text(source_name).unwrap();
-			ctx.set_font_size(store.font_size);
-		}*/
 	}
 
 	{
 		store.scroll_bar.bar_height = h as f64 - store.scroll_bar.bar_padding * 2.0;
-
-		store.scroll_bar.scroll_perc = store.get_scroll_percentage(store.visible_lines);
-
-		store.scroll_bar.thumb_rel_offset = f64::round(
-			(store.scroll_bar.bar_height
-				- store.scroll_bar.thumb_height
-				- store.scroll_bar.thumb_margin * 2.0)
-				* store.scroll_bar.scroll_perc,
-		) + store.scroll_bar.thumb_margin;
-
 		store.scroll_bar.x = w as f64 - store.scroll_bar.bar_width - store.scroll_bar.bar_padding;
 		store.scroll_bar.y = store.scroll_bar.bar_padding;
+		update_scroll_thumb(store);
 
-		store.scroll_bar.thumb_width =
-			store.scroll_bar.bar_width - 2.0 * store.scroll_bar.thumb_margin;
-		store.scroll_bar.thumb_x = store.scroll_bar.x + store.scroll_bar.thumb_margin;
-		store.scroll_bar.thumb_y = store.scroll_bar.y + store.scroll_bar.thumb_rel_offset;
-
-		ctx.set_source_rgb(0.7, 0.7, 0.7);
+		let scroll_bar = store.theme.scroll_bar;
+		ctx.set_source_rgb(scroll_bar.0, scroll_bar.1, scroll_bar.2);
 		ctx.rectangle(
 			store.scroll_bar.x,
 			store.scroll_bar.y,
@@ -446,12 +603,69 @@ fn draw(
 		);
 		ctx.fill().unwrap();
 
-		ctx.set_source_rgb(0.3, 0.3, 0.3);
+		//Inert bar without thumb if there is nothing to scroll
+		if !store.fits_on_screen(store.visible_lines) {
+			draw_density(store, ctx);
+
+			//Translucent, so the density marks below stay visible
+			let scroll_thumb = store.theme.scroll_thumb;
+			ctx.set_source_rgba(scroll_thumb.0, scroll_thumb.1, scroll_thumb.2, 0.7);
+			ctx.rectangle(
+				store.scroll_bar.thumb_x,
+				store.scroll_bar.thumb_y,
+				store.scroll_bar.thumb_width,
+				store.scroll_bar.thumb_height,
+			);
+			ctx.fill().unwrap();
+		}
+	}
+
+	store.content_width = content_width;
+	store.h_scroll_bar.bar_width = message_width;
+	if store.wrap_chars == 0 {
+		//Keep the offset in range if the longest message scrolled out of view
+		store.scroll_horz(0.0);
+	} else {
+		store.h_offset = 0.0;
+	}
+
+	if store.wrap_chars == 0 && store.content_width > store.h_scroll_bar.bar_width {
+		let max_offset = store.content_width - store.h_scroll_bar.bar_width;
+		store.h_scroll_bar.scroll_perc = store.h_offset / max_offset;
+
+		store.h_scroll_bar.x = message_x;
+		store.h_scroll_bar.y =
+			h as f64 - store.h_scroll_bar.bar_height - store.h_scroll_bar.bar_padding;
+
+		store.h_scroll_bar.thumb_rel_offset = f64::round(
+			(store.h_scroll_bar.bar_width
+				- store.h_scroll_bar.thumb_width
+				- store.h_scroll_bar.thumb_margin * 2.0)
+				* store.h_scroll_bar.scroll_perc,
+		) + store.h_scroll_bar.thumb_margin;
+
+		store.h_scroll_bar.thumb_height =
+			store.h_scroll_bar.bar_height - 2.0 * store.h_scroll_bar.thumb_margin;
+		store.h_scroll_bar.thumb_x = store.h_scroll_bar.x + store.h_scroll_bar.thumb_rel_offset;
+		store.h_scroll_bar.thumb_y = store.h_scroll_bar.y + store.h_scroll_bar.thumb_margin;
+
+		let scroll_bar = store.theme.scroll_bar;
+		ctx.set_source_rgb(scroll_bar.0, scroll_bar.1, scroll_bar.2);
+		ctx.rectangle(
+			store.h_scroll_bar.x,
+			store.h_scroll_bar.y,
+			store.h_scroll_bar.bar_width,
+			store.h_scroll_bar.bar_height,
+		);
+		ctx.fill().unwrap();
+
+		let scroll_thumb = store.theme.scroll_thumb;
+		ctx.set_source_rgb(scroll_thumb.0, scroll_thumb.1, scroll_thumb.2);
 		ctx.rectangle(
-			store.scroll_bar.thumb_x,
-			store.scroll_bar.thumb_y,
-			store.scroll_bar.thumb_width,
-			store.scroll_bar.thumb_height,
+			store.h_scroll_bar.thumb_x,
+			store.h_scroll_bar.thumb_y,
+			store.h_scroll_bar.thumb_width,
+			store.h_scroll_bar.thumb_height,
 		);
 		ctx.fill().unwrap();
 	}
@@ -488,15 +702,30 @@ fn handle_evt_scroll(
 	drawing_area: &DrawingArea,
 	evt: &gdk::EventScroll,
 ) -> gtk::Inhibit {
-	let scroll_speed = 3;
+	let scroll_speed = store.scroll_speed;
 	let mut dirty = false;
+	//Shift + wheel scrolls long messages horizontally
+	let horizontal = evt.state().contains(gdk::ModifierType::SHIFT_MASK);
+	let h_scroll_speed = scroll_speed as f64 * store.line_spacing;
 	match evt.direction() {
+		gdk::ScrollDirection::Up if horizontal => {
+			dirty = store.scroll_horz(-h_scroll_speed);
+		}
+		gdk::ScrollDirection::Down if horizontal => {
+			dirty = store.scroll_horz(h_scroll_speed);
+		}
 		gdk::ScrollDirection::Up => {
 			dirty = store.scroll(-scroll_speed, store.visible_lines);
 		}
 		gdk::ScrollDirection::Down => {
 			dirty = store.scroll(scroll_speed, store.visible_lines);
 		}
+		gdk::ScrollDirection::Left => {
+			dirty = store.scroll_horz(-h_scroll_speed);
+		}
+		gdk::ScrollDirection::Right => {
+			dirty = store.scroll_horz(h_scroll_speed);
+		}
 		_ => (),
 	}
 
@@ -509,6 +738,444 @@ fn handle_evt_scroll(
 	gtk::Inhibit(false)
 }
 
+const THUMB_MIN_HEIGHT: f64 = 20.0; //pixels, keeps the thumb grabbable in huge logs
+const PAGE_REPEAT_DELAY: u32 = 5; //intervals before holding the mouse button repeats paging
+const PAGE_REPEAT_INTERVAL: u64 = 50; //milliseconds
+const SEARCH_POLL_INTERVAL: u64 = 50; //milliseconds
+
+//Thumb size proportional to the visible part of the list, position from the scroll percentage
+fn update_scroll_thumb(store: &mut LogStoreLinear) {
+	let (_, track_height) = density_track(store);
+	let track_height = f64::max(0.0, track_height);
+	store.scroll_bar.thumb_height = if store.entry_count == 0 {
+		track_height
+	} else {
+		(track_height * store.visible_lines as f64 / store.entry_count as f64)
+			.clamp(f64::min(THUMB_MIN_HEIGHT, track_height), track_height)
+	};
+
+	store.scroll_bar.scroll_perc = store.get_scroll_percentage(store.visible_lines);
+	store.scroll_bar.thumb_rel_offset =
+		f64::round((track_height - store.scroll_bar.thumb_height) * store.scroll_bar.scroll_perc)
+			+ store.scroll_bar.thumb_margin;
+
+	store.scroll_bar.thumb_width = store.scroll_bar.bar_width - 2.0 * store.scroll_bar.thumb_margin;
+	store.scroll_bar.thumb_x = store.scroll_bar.x + store.scroll_bar.thumb_margin;
+	store.scroll_bar.thumb_y = store.scroll_bar.y + store.scroll_bar.thumb_rel_offset;
+}
+
+//Scrolls one page towards the pointer held on the scroll bar track.
+//Returns false once the thumb has reached the pointer.
+fn page_scroll_step(store: &mut LogStoreLinear) -> bool {
+	let (direction, pointer_y) = match &store.page_scroll {
+		Some(page_scroll) => (page_scroll.direction, page_scroll.pointer_y),
+		None => return false,
+	};
+	let reached = if direction < 0 {
+		pointer_y >= store.scroll_bar.thumb_y
+	} else {
+		pointer_y <= store.scroll_bar.thumb_y + store.scroll_bar.thumb_height
+	};
+	if reached {
+		return false;
+	}
+	let page = std::cmp::max(1, store.visible_lines.saturating_sub(1)) as i64;
+	let scrolled = store.scroll(direction * page, store.visible_lines);
+	store.clamp_viewport_bottom(store.visible_lines);
+	update_scroll_thumb(store);
+	scrolled
+}
+
+//Shows the progress of the background search until it is applied or replaced by a small one
+fn watch_search(
+	store_rc: &Rc<RefCell<LogStoreLinear>>,
+	drawing_area: &DrawingArea,
+	searching_label: &gtk::Label,
+) {
+	searching_label.set_text("Searching…");
+	searching_label.show();
+	let store_rc = store_rc.clone();
+	let drawing_area = drawing_area.clone();
+	let searching_label = searching_label.clone();
+	glib::timeout_add_local(
+		std::time::Duration::from_millis(SEARCH_POLL_INTERVAL),
+		move || {
+			let mut store = store_rc.borrow_mut();
+			if store.poll_search() {
+				searching_label.hide();
+				drawing_area.queue_draw();
+				return glib::Continue(false);
+			}
+			if let Some(job) = &store.search_job {
+				let (searched, total, matched) = job.progress();
+				searching_label.set_text(&format!(
+					"Searching… {} % ({} matches so far)",
+					searched * 100 / std::cmp::max(1, total),
+					matched
+				));
+			}
+			glib::Continue(true)
+		},
+	);
+}
+
+//Repeats paging while the mouse button stays down on the track, see `handle_evt_release`
+fn start_page_repeat(store_rc: &Rc<RefCell<LogStoreLinear>>, drawing_area: &DrawingArea) {
+	let timer = {
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		let mut intervals = 0;
+		glib::timeout_add_local(
+			std::time::Duration::from_millis(PAGE_REPEAT_INTERVAL),
+			move || {
+				intervals += 1;
+				if intervals > PAGE_REPEAT_DELAY && page_scroll_step(&mut store_rc.borrow_mut()) {
+					drawing_area.queue_draw();
+				}
+				//Keeps running until removed, so the id stays valid
+				glib::Continue(true)
+			},
+		)
+	};
+	if let Some(page_scroll) = &mut store_rc.borrow_mut().page_scroll {
+		page_scroll.timer = Some(timer);
+	} else {
+		timer.remove();
+	}
+}
+
+const TIMELINE_HEIGHT: i32 = 90; //pixels
+const TIMELINE_PADDING: f64 = 5.0;
+const TIMELINE_MIN_BAR_WIDTH: f64 = 3.0;
+
+//Plot area of the timeline: x, y, width, height
+fn timeline_plot(store: &LogStoreLinear, timeline_area: &DrawingArea) -> (f64, f64, f64, f64) {
+	let label_height = store.line_spacing;
+	let w = timeline_area.allocated_width() as f64;
+	let h = timeline_area.allocated_height() as f64;
+	(
+		TIMELINE_PADDING,
+		TIMELINE_PADDING,
+		f64::max(0.0, w - TIMELINE_PADDING * 2.0),
+		f64::max(0.0, h - TIMELINE_PADDING * 2.0 - label_height),
+	)
+}
+
+//Histogram of entries over time stacked by severity, with the selected time range, viewport and anchor
+fn draw_timeline(
+	store: &mut LogStoreLinear,
+	timeline_area: &DrawingArea,
+	ctx: &cairo::Context,
+) -> gtk::Inhibit {
+	let header_background = store.theme.header_background;
+	ctx.set_source_rgb(
+		header_background.0,
+		header_background.1,
+		header_background.2,
+	);
+	ctx.paint().unwrap();
+
+	let (plot_x, plot_y, plot_w, plot_h) = timeline_plot(store, timeline_area);
+	let max_bins = (plot_w / TIMELINE_MIN_BAR_WIDTH) as usize;
+	if max_bins < 2 || plot_h <= 0.0 {
+		return gtk::Inhibit(false);
+	}
+	if store
+		.timeline
+		.as_ref()
+		.is_none_or(|histogram| histogram.max_bins != max_bins)
+	{
+		store.timeline = timeline::histogram(store, max_bins);
+	}
+	let histogram = if let Some(histogram) = &store.timeline {
+		histogram
+	} else {
+		return gtk::Inhibit(false); //Nothing to show
+	};
+	let time_to_x =
+		|timestamp: &chrono::DateTime<chrono::Utc>| plot_x + histogram.fraction(timestamp) * plot_w;
+
+	//Stacked from the bottom, most severe first so that rare errors stand out
+	let bar_w = plot_w / histogram.bins.len() as f64;
+	let max_total = histogram
+		.bins
+		.iter()
+		.map(|bin| bin.iter().sum::<u32>())
+		.max()
+		.unwrap_or(0);
+	for (idx, bin) in histogram.bins.iter().enumerate() {
+		let mut y = plot_y + plot_h;
+		for (severity, count) in bin.iter().enumerate() {
+			if *count == 0 {
+				continue;
+			}
+			let height = plot_h * *count as f64 / max_total as f64;
+			y -= height;
+			let color = store.theme.severity[severity].mark();
+			ctx.set_source_rgb(color.0, color.1, color.2);
+			ctx.rectangle(
+				plot_x + idx as f64 * bar_w,
+				y,
+				f64::max(1.0, bar_w - 1.0),
+				height,
+			);
+			ctx.fill().unwrap();
+		}
+	}
+
+	let selected = store.theme.selected;
+	let selection = match store.timeline_brush {
+		Some((x0, x1)) => Some((f64::min(x0, x1), f64::max(x0, x1))),
+		None => store
+			.time_range
+			.map(|(start, end)| (time_to_x(&start), time_to_x(&end))),
+	};
+	if let Some((x0, x1)) = selection {
+		ctx.set_source_rgba(selected.0, selected.1, selected.2, 0.5);
+		ctx.rectangle(x0, plot_y, f64::max(1.0, x1 - x0), plot_h);
+		ctx.fill().unwrap();
+	}
+
+	//Time span of the entries in the viewport, which need not be sorted
+	if let Some(first) = store.rel_to_abs_offset(0) {
+		let mut offset = first;
+		let mut min = store.store[first].timestamp;
+		let mut max = min;
+		for _ in 1..store.visible_lines {
+			//Note: The last element points to itself, so it's safe in all cases
+			offset = store.store[offset].next_offset as usize;
+			min = min.min(store.store[offset].timestamp);
+			max = max.max(store.store[offset].timestamp);
+		}
+		let scroll_thumb = store.theme.scroll_thumb;
+		ctx.set_source_rgba(scroll_thumb.0, scroll_thumb.1, scroll_thumb.2, 0.3);
+		let x0 = time_to_x(&min.to_datetime());
+		ctx.rectangle(
+			x0,
+			plot_y,
+			f64::max(2.0, time_to_x(&max.to_datetime()) - x0),
+			plot_h,
+		);
+		ctx.fill().unwrap();
+	}
+
+	if let Some(anchor_offset) = store.anchor_offset {
+		let bookmark = store.theme.bookmark;
+		ctx.set_source_rgb(bookmark.0, bookmark.1, bookmark.2);
+		let x = time_to_x(&store.store[anchor_offset].datetime()).round() + 0.5;
+		ctx.set_line_width(1.0);
+		ctx.move_to(x, plot_y);
+		ctx.line_to(x, plot_y + plot_h);
+		ctx.stroke().unwrap();
+	}
+
+	//Axis labels: start, bin width and end
+	let layout = timeline_area.create_pango_layout(None);
+	layout.set_font_description(Some(&store.font));
+	let style_ctx = timeline_area.style_context();
+	let label_y = plot_y + plot_h;
+	let label_color = store.theme.header_foreground;
+	let format = histogram.label_format();
+	let start_label = settings::format_timestamp(&histogram.start, format, &store.time_zone);
+	let end_label = settings::format_timestamp(&histogram.end(), format, &store.time_zone);
+	let bin_label = format!("1 bar = {}", timeline::format_bin_width(histogram.bin_ms));
+	draw_text(
+		ctx,
+		&style_ctx,
+		&layout,
+		(plot_x, label_y),
+		&start_label,
+		label_color,
+	);
+	let bin_x = plot_x + (plot_w - text_width(&layout, &bin_label)) / 2.0;
+	draw_text(
+		ctx,
+		&style_ctx,
+		&layout,
+		(bin_x, label_y),
+		&bin_label,
+		label_color,
+	);
+	let end_x = plot_x + plot_w - text_width(&layout, &end_label);
+	draw_text(
+		ctx,
+		&style_ctx,
+		&layout,
+		(end_x, label_y),
+		&end_label,
+		label_color,
+	);
+
+	gtk::Inhibit(false)
+}
+
+//Fills the time range entries with the range of the store, empty if there is none
+fn show_time_range(start_entry: &gtk::Entry, end_entry: &gtk::Entry, store: &LogStoreLinear) {
+	let format = |timestamp| {
+		settings::format_timestamp(timestamp, &store.timestamp_format, &store.time_zone)
+	};
+	match &store.time_range {
+		Some((start, end)) => {
+			start_entry.set_text(&format(start));
+			end_entry.set_text(&format(end));
+		}
+		None => {
+			start_entry.set_text("");
+			end_entry.set_text("");
+		}
+	}
+	start_entry.style_context().remove_class("error");
+	end_entry.style_context().remove_class("error");
+}
+
+//An empty entry leaves that end of the range open; invalid input is marked and not applied
+fn apply_time_range_entries(
+	start_entry: &gtk::Entry,
+	end_entry: &gtk::Entry,
+	store: &mut LogStoreLinear,
+	drawing_area: &DrawingArea,
+) {
+	let reference_offset = store.anchor_offset.unwrap_or(store.first_offset);
+	//Not necessarily the first and last entry, see `SortMode`
+	let timestamps = store.store.iter().map(|entry| entry.timestamp);
+	let (first, last) = match (timestamps.clone().min(), timestamps.max()) {
+		(Some(first), Some(last)) => (first.to_datetime(), last.to_datetime()),
+		_ => return,
+	};
+	let reference_date =
+		settings::local_date(&store.store[reference_offset].datetime(), &store.time_zone);
+	let parse = |entry: &gtk::Entry, open_end| {
+		let text = entry.text();
+		if text.trim().is_empty() {
+			return Some(open_end);
+		}
+		let timestamp = settings::parse_timestamp(
+			&text,
+			&store.timestamp_format,
+			&store.time_zone,
+			reference_date,
+		);
+		if timestamp.is_none() {
+			entry.style_context().add_class("error");
+		}
+		timestamp
+	};
+	start_entry.style_context().remove_class("error");
+	end_entry.style_context().remove_class("error");
+	let start = parse(start_entry, first);
+	let end = parse(end_entry, last);
+	if let (Some(start), Some(end)) = (start, end) {
+		let open = start_entry.text().trim().is_empty() && end_entry.text().trim().is_empty();
+		store.set_time_range(if open { None } else { Some((start, end)) });
+		drawing_area.queue_draw();
+	}
+}
+
+//Dragging in the timeline selects a time range, a click without dragging clears it
+fn handle_timeline_release(
+	store: &mut LogStoreLinear,
+	timeline_area: &DrawingArea,
+	drawing_area: &DrawingArea,
+) {
+	let (x0, x1) = match store.timeline_brush.take() {
+		Some((x0, x1)) => (f64::min(x0, x1), f64::max(x0, x1)),
+		None => return,
+	};
+	let (plot_x, _, plot_w, _) = timeline_plot(store, timeline_area);
+	let time_range = match &store.timeline {
+		Some(histogram) if x1 - x0 >= TIMELINE_MIN_BAR_WIDTH && plot_w > 0.0 => Some((
+			histogram.timestamp((x0 - plot_x) / plot_w),
+			histogram.timestamp((x1 - plot_x) / plot_w),
+		)),
+		_ => None,
+	};
+	if time_range.is_some() || store.time_range.is_some() {
+		store.set_time_range(time_range);
+	}
+	timeline_area.queue_draw();
+	drawing_area.queue_draw();
+}
+
+//Track of the vertical scroll bar that the density overview and track clicks map onto
+fn density_track(store: &LogStoreLinear) -> (f64, f64) {
+	let top = store.scroll_bar.y + store.scroll_bar.thumb_margin;
+	let height = store.scroll_bar.bar_height - store.scroll_bar.thumb_margin * 2.0;
+	(top, height)
+}
+
+//Heat-map of visible critical, error and warning entries and search hits along the scroll bar
+fn draw_density(store: &LogStoreLinear, ctx: &cairo::Context) {
+	let (top, height) = density_track(store);
+	let blocks = store.density.len();
+	if blocks == 0 || height < 1.0 {
+		return;
+	}
+	let pixel_rows = height as usize;
+	let x = store.scroll_bar.thumb_x;
+	let hits_width = 4.0;
+	let severity_width = store.scroll_bar.thumb_width - hits_width - 1.0;
+
+	//Sum up the blocks of each pixel row; a block may also span several rows
+	let rows: Vec<log_store::DensityBlock> = (0..pixel_rows)
+		.map(|row| {
+			let first = row * blocks / pixel_rows;
+			let last = std::cmp::max(first + 1, (row + 1) * blocks / pixel_rows);
+			store.density[first..last.min(blocks)].iter().fold(
+				log_store::DensityBlock::default(),
+				|sum, block| log_store::DensityBlock {
+					critical: sum.critical + block.critical,
+					error: sum.error + block.error,
+					warning: sum.warning + block.warning,
+					search_hits: sum.search_hits + block.search_hits,
+				},
+			)
+		})
+		.collect();
+	let max_severity = rows
+		.iter()
+		.map(|row| row.critical + row.error + row.warning)
+		.max()
+		.unwrap_or(0);
+	let max_hits = rows.iter().map(|row| row.search_hits).max().unwrap_or(0);
+	//Even a single entry must stay visible next to dense clusters
+	let alpha = |count: u32, max: u32| 0.35 + 0.65 * count as f64 / max as f64;
+
+	for (row, counts) in rows.iter().enumerate() {
+		let y = top + row as f64;
+		let severity_count = counts.critical + counts.error + counts.warning;
+		if severity_count > 0 {
+			//Most severe level wins the color, the total count the intensity
+			let color = if counts.critical > 0 {
+				store.theme.severity(&model::LogLevel::Critical)
+			} else if counts.error > 0 {
+				store.theme.severity(&model::LogLevel::Error)
+			} else {
+				store.theme.severity(&model::LogLevel::Warning)
+			}
+			.mark();
+			ctx.set_source_rgba(
+				color.0,
+				color.1,
+				color.2,
+				alpha(severity_count, max_severity),
+			);
+			ctx.rectangle(x, y, severity_width, 1.0);
+			ctx.fill().unwrap();
+		}
+		if counts.search_hits > 0 {
+			let color = store.theme.search_hit;
+			ctx.set_source_rgba(
+				color.0,
+				color.1,
+				color.2,
+				alpha(counts.search_hits, max_hits),
+			);
+			ctx.rectangle(x + severity_width + 1.0, y, hits_width, 1.0);
+			ctx.fill().unwrap();
+		}
+	}
+}
+
 fn handle_evt_press(
 	store: &mut LogStoreLinear,
 	drawing_area: &DrawingArea,
@@ -518,7 +1185,8 @@ fn handle_evt_press(
 	//log::info!("PRESS root: {:?}", evt.get_root());
 
 	store.mouse_down = true;
-	if evt.position().0 >= store.scroll_bar.thumb_x
+	if !store.fits_on_screen(store.visible_lines)
+		&& evt.position().0 >= store.scroll_bar.thumb_x
 		&& evt.position().0 <= store.scroll_bar.thumb_x + store.scroll_bar.thumb_width
 		&& evt.position().1 >= store.scroll_bar.thumb_y
 		&& evt.position().1 <= store.scroll_bar.thumb_y + store.scroll_bar.thumb_height
@@ -527,11 +1195,76 @@ fn handle_evt_press(
 		store.thumb_drag_x = evt.position().0 - store.scroll_bar.thumb_x;
 		store.thumb_drag_y = evt.position().1 - store.scroll_bar.thumb_y;
 		store.hover_line = None;
-	} else if !(evt.position().0 < store.border_left || evt.position().1 < store.border_top) {
-		let line = ((evt.position().1 - store.border_top) / store.line_spacing) as usize;
-		if line < store.visible_lines {
+	} else if store.wrap_chars == 0
+		&& store.content_width > store.h_scroll_bar.bar_width
+		&& evt.position().0 >= store.h_scroll_bar.thumb_x
+		&& evt.position().0 <= store.h_scroll_bar.thumb_x + store.h_scroll_bar.thumb_width
+		&& evt.position().1 >= store.h_scroll_bar.thumb_y
+		&& evt.position().1 <= store.h_scroll_bar.thumb_y + store.h_scroll_bar.thumb_height
+	{
+		store.h_thumb_drag = true;
+		store.thumb_drag_x = evt.position().0 - store.h_scroll_bar.thumb_x;
+		store.thumb_drag_y = evt.position().1 - store.h_scroll_bar.thumb_y;
+		store.hover_line = None;
+	} else if evt.position().0 >= store.scroll_bar.x
+		&& evt.position().0 <= store.scroll_bar.x + store.scroll_bar.bar_width
+		&& evt.position().1 >= store.scroll_bar.y
+		&& evt.position().1 <= store.scroll_bar.y + store.scroll_bar.bar_height
+	{
+		if store.fits_on_screen(store.visible_lines)
+			|| evt.event_type() != gdk::EventType::ButtonPress
+		{
+			//Nothing to scroll, the bar is inert; multi-clicks just continue the first one
+			return gtk::Inhibit(false);
+		}
+		let (top, height) = density_track(store);
+		if !store.pressed_shift && evt.button() == 1 {
+			//Click into the track, page towards the pointer
+			let direction = if evt.position().1 < store.scroll_bar.thumb_y {
+				-1
+			} else {
+				1
+			};
+			store.page_scroll = Some(log_store::PageScroll {
+				direction,
+				pointer_y: evt.position().1,
+				timer: None,
+			});
+			store.hover_line = None;
+			if page_scroll_step(store) {
+				drawing_area.queue_draw();
+			}
+		} else if store.entry_count > 0 && height > 0.0 {
+			//Shift or middle click into the density overview, center the entry at that position
+			let perc = ((evt.position().1 - top) / height).clamp(0.0, 1.0);
+			let entry_id = std::cmp::min(
+				(perc * store.entry_count as f64) as usize,
+				store.entry_count - 1,
+			);
+			if let Some(offset) = store.entry_id_to_offset(entry_id) {
+				store.jump_to(offset);
+				drawing_area.queue_draw();
+			}
+		}
+	} else if evt.position().1 >= store.border_top
+		&& evt.position().1 < store.border_top + store.header_height
+	{
+		//Click into the column header, resize column if a separator was hit
+		store.column_drag = store.column_separator_at(evt.position().0);
+	} else if evt.position().0 >= store.border_left {
+		if let Some(line) = store.line_at(evt.position().1) {
 			let clicked_line = store.rel_to_abs_offset(line);
 
+			if evt.event_type() == gdk::EventType::DoubleButtonPress {
+				//Selection was already handled by the preceding single click
+				if let Some(clicked_line_val) = clicked_line {
+					if store.toggle_expanded(clicked_line_val) {
+						drawing_area.queue_draw();
+					}
+				}
+				return gtk::Inhibit(false);
+			}
+
 			if let Some(clicked_line_val) = clicked_line {
 				if !store.pressed_shift && !store.pressed_ctrl {
 					store.selected_single.clear();
@@ -595,8 +1328,17 @@ fn handle_evt_release(
 	//log::info!("RELEASE");
 	store.mouse_down = false;
 	store.thumb_drag = false;
+	store.h_thumb_drag = false;
 	store.thumb_drag_x = 0.0;
 	store.thumb_drag_y = 0.0;
+	store.column_drag = None;
+	if let Some(timer) = store
+		.page_scroll
+		.take()
+		.and_then(|page_scroll| page_scroll.timer)
+	{
+		timer.remove();
+	}
 	gtk::Inhibit(false)
 }
 
@@ -606,7 +1348,23 @@ fn handle_evt_motion(
 	drawing_area: &DrawingArea,
 	evt: &gdk::EventMotion,
 ) -> gtk::Inhibit {
-	if store.thumb_drag {
+	if let Some(page_scroll) = &mut store.page_scroll {
+		page_scroll.pointer_y = evt.position().1;
+		return gtk::Inhibit(false);
+	}
+	if let Some(idx) = store.column_drag {
+		if let Some((_, column_x, _)) = store
+			.column_positions()
+			.into_iter()
+			.find(|(column_idx, _, _)| *column_idx == idx)
+		{
+			store.columns[idx].user_width = Some(f64::max(
+				columns::COLUMN_MIN_WIDTH,
+				evt.position().0 - column_x + columns::COLUMN_PADDING / 2.0,
+			));
+			drawing_area.queue_draw();
+		}
+	} else if store.thumb_drag {
 		store.scroll_bar.thumb_y = evt.position().1 - store.thumb_drag_y;
 		store.scroll_bar.thumb_rel_offset = store.scroll_bar.thumb_y - store.scroll_bar.y;
 		store.scroll_bar.scroll_perc = (store.scroll_bar.thumb_rel_offset
@@ -622,22 +1380,47 @@ fn handle_evt_motion(
 		store.viewport_offset = store
 			.percentage_to_offset(store.scroll_bar.scroll_perc, store.visible_lines)
 			.unwrap_or(0);
+		store.viewport_row = 0;
+		store.clamp_viewport_bottom(store.visible_lines);
 		log::trace!("MOTION {:?}", evt.position());
 		drawing_area.queue_draw();
+	} else if store.h_thumb_drag {
+		store.h_scroll_bar.thumb_x = evt.position().0 - store.thumb_drag_x;
+		store.h_scroll_bar.thumb_rel_offset = store.h_scroll_bar.thumb_x - store.h_scroll_bar.x;
+		store.h_scroll_bar.scroll_perc = ((store.h_scroll_bar.thumb_rel_offset
+			- store.h_scroll_bar.thumb_margin)
+			/ (store.h_scroll_bar.bar_width
+				- store.h_scroll_bar.thumb_width
+				- store.h_scroll_bar.thumb_margin * 2.0))
+			.clamp(0.0, 1.0);
+		store.h_offset = f64::max(0.0, store.content_width - store.h_scroll_bar.bar_width)
+			* store.h_scroll_bar.scroll_perc;
+		drawing_area.queue_draw();
 	} else {
 		let current_hover = {
-			if evt.position().0 < store.border_left || evt.position().1 < store.border_top {
+			if evt.position().0 < store.border_left {
 				None
 			} else {
-				let line = ((evt.position().1 - store.border_top) / store.line_spacing) as usize;
-				if line >= store.visible_lines {
-					None
-				} else {
-					Some(line)
-				}
+				store.line_at(evt.position().1)
 			}
 		};
 
+		//Indicate that column separators in the header can be dragged
+		let on_separator = evt.position().1 >= store.border_top
+			&& evt.position().1 < store.border_top + store.header_height
+			&& store.column_separator_at(evt.position().0).is_some();
+		if on_separator != store.column_resize_cursor {
+			store.column_resize_cursor = on_separator;
+			if let Some(window) = drawing_area.window() {
+				let cursor = if on_separator {
+					gdk::Cursor::from_name(&drawing_area.display(), "col-resize")
+				} else {
+					None
+				};
+				window.set_cursor(cursor.as_ref());
+			}
+		}
+
 		if current_hover != store.hover_line {
 			if let Some(line) = current_hover {
 				if let Some(hover_entry) = store.rel_to_abs_offset(line) {
@@ -683,12 +1466,70 @@ const PREFERRED_FONTS: [&str; 2] = [
 	"Lucida Console", // Ships with Windows, like "Calibri" (which is unfortunately not monospace)
 	"DejaVu Sans Mono", // Generally available on Linux
 ];
+const DEFAULT_FONT_SIZE: f64 = 14.0; //pixels
+
+//Asks for the password of an encrypted sfile; None if cancelled. Also returns whether to remember it.
+fn prompt_password(
+	window: &gtk::ApplicationWindow,
+	file_path: &std::path::Path,
+) -> Option<(String, bool)> {
+	let dialog = gtk::Dialog::with_buttons(
+		Some("Password required"),
+		Some(window),
+		gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+		&[
+			("Cancel", gtk::ResponseType::Cancel),
+			("Open", gtk::ResponseType::Ok),
+		],
+	);
+	dialog.set_default_response(gtk::ResponseType::Ok);
+	let content = gtk::Box::new(Orientation::Vertical, 6);
+	content.set_border_width(10);
+	let label = gtk::Label::new(Some(&format!(
+		"{} is encrypted and none of the known passwords fits.",
+		file_path
+			.file_name()
+			.unwrap_or(file_path.as_os_str())
+			.to_string_lossy()
+	)));
+	label.set_halign(gtk::Align::Start);
+	content.pack_start(&label, false, false, 0);
+	let password_entry = gtk::Entry::new();
+	password_entry.set_visibility(false);
+	password_entry.set_activates_default(true);
+	content.pack_start(&password_entry, false, false, 0);
+	let remember_check = gtk::CheckButton::with_label("Remember password in the system keyring");
+	content.pack_start(&remember_check, false, false, 0);
+	dialog.content_area().add(&content);
+	dialog.show_all();
+
+	let response = dialog.run();
+	let result = (response == gtk::ResponseType::Ok).then(|| {
+		(
+			password_entry.text().to_string(),
+			remember_check.is_active(),
+		)
+	});
+	dialog.close();
+	result
+}
+
+fn default_font(family: &str) -> pango::FontDescription {
+	let mut font = pango::FontDescription::new();
+	font.set_family(family);
+	font.set_absolute_size(DEFAULT_FONT_SIZE * pango::SCALE as f64);
+	font
+}
 
 //--------------------------------------------------------------------------------------------------
 //--------------------------------------------------------------------------------------------------
 //--------------------------------------------------------------------------------------------------
 
-fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
+fn build_ui(
+	application: &gtk::Application,
+	file_paths: &[std::path::PathBuf],
+	cli_passwords: &[String],
+) {
 	log::info!("File paths: {:?}", file_paths);
 	let file_path = if !file_paths.is_empty() {
 		if file_paths.len() > 1 {
@@ -712,6 +1553,15 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	window.set_position(gtk::WindowPosition::Center);
 	window.set_default_size(600, 400);
 
+	let settings = settings::Settings::load().unwrap_or_else(|err| {
+		log::warn!(
+			"Could not load settings {:?}: {}",
+			settings::Settings::path(),
+			err
+		);
+		settings::Settings::default()
+	});
+
 	let pango_ctx = window.pango_context();
 	let preferred_font = pango_ctx
 		.font_map()
@@ -830,17 +1680,50 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 
 	let mut dialog_vec: Vec<gtk::MessageDialog> = Vec::<gtk::MessageDialog>::new();
 
+	let mut session_path = None; //Only set if a file was opened successfully
+
+	let sfile_layout =
+		parse::sfile_layout::SfileLayout::load(&settings::config_dir().join("sfile_layout.toml"));
+	let mut messages = message_arena::MessageArenaBuilder::default();
 	let log_source_root = if let Some(file_path) = file_path {
 		let now = Instant::now();
-		let root = parse::from_file(file_path);
-		let elapsed = now.elapsed();
-		log::info!(
+		let candidates: Vec<String> = cli_passwords
+			.iter()
+			.cloned()
+			.chain(passwords::sfile_passwords())
+			.collect();
+		let mut remember_errors = Vec::new();
+		let root = passwords::try_passwords(
+			candidates,
+			|candidates| parse::from_file(file_path, &sfile_layout, candidates, &mut messages),
+			|| prompt_password(&window, file_path),
+			|password| {
+				if let Err(err) = passwords::remember_sfile_password(password) {
+					log::warn!("Could not store sfile password in keyring: {}", err);
+					remember_errors.push(err);
+				}
+			},
+		);
+		for err in remember_errors {
+			dialog_vec.push(gtk::MessageDialog::new(
+				Some(&window),
+				gtk::DialogFlags::empty(),
+				gtk::MessageType::Warning,
+				gtk::ButtonsType::Ok,
+				&format!("The password could not be remembered: {}", err),
+			));
+		}
+		let elapsed = now.elapsed();
+		log::info!(
 			"Time to parse file: {}ms",
 			elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64
 		);
 
 		match root {
-			Ok(root) => root,
+			Ok(root) => {
+				session_path = Some(session::session_path(file_path));
+				root
+			}
 			Err(err) => {
 				let error_str = format!("Error: {}", err);
 				dialog_vec.push(gtk::MessageDialog::new(
@@ -850,17 +1733,23 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 					gtk::ButtonsType::Ok,
 					&error_str,
 				));
-				log_source_root
+				LogSourceExt::from_source(log_source_root, &mut messages)
 			}
 		}
 	} else {
-		log_source_root
+		LogSourceExt::from_source(log_source_root, &mut messages)
 	};
 
 	// Create log store as Refcounted RefCell to be used in closures ------------------------
 
-	let store = LogStoreLinear {
+	let mut store = LogStoreLinear {
 		store: Vec::<LogEntryExt>::new(),
+		file_positions: Vec::new(),
+		sort_mode: log_store::SortMode::Timestamp,
+		place_boot_relative: false,
+		corrections: Vec::new(),
+		corrections_enabled: true,
+		messages: Default::default(),
 		entry_count: 0,
 		first_offset: 0,
 		last_offset: 0,
@@ -878,14 +1767,29 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		selected_single_last: None,
 		selected_range: None,
 
+		bookmarks: std::collections::BTreeSet::new(),
+		expanded: std::collections::HashSet::new(),
+
+		field_filter: None,
+		time_range: None,
+		timeline: None,
+		timeline_brush: None,
+
 		pressed_shift: false,
 		pressed_ctrl: false,
 
 		log_sources: std::collections::HashMap::<u32, String>::new(),
+		source_colors: std::collections::HashMap::new(),
+
+		columns: Vec::new(), //populated after the custom fields are known
+		column_drag: None,
+		column_resize_cursor: false,
+		header_height: 20.0, //calculate dynamically
 
 		visible_lines: 0,
 		hover_line: None,
 		viewport_offset: 0,
+		viewport_row: 0,
 		mouse_down: false,
 		thumb_drag: false,
 		thumb_drag_x: 0.0,
@@ -895,7 +1799,18 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		border_top: 10.0,
 		border_bottom: 10.0,
 		line_spacing: 20.0,
-		font_size: 14.0,
+		font: default_font(preferred_font),
+		row_padding: 2.0,
+		scroll_speed: 3,
+		timestamp_format: String::new(),
+		time_zone: settings::TimeZone::Utc,
+
+		theme_kind: theme::ThemeKind::System,
+		system_dark: system_prefers_dark(),
+		severity_overrides: [None; 6],
+		theme: theme::Theme::light(), //see `update_theme`
+
+		copy_format: settings::Settings::default().copy_format(),
 
 		scroll_bar: ScrollBarVert {
 			x: 0.0,
@@ -908,21 +1823,62 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 			thumb_x: 0.0, //calculate dynamically
 			thumb_y: 0.0, //calculate dynamically
 			thumb_margin: 3.0,
-			thumb_width: 0.0, //calculate dynamically
-			thumb_height: 50.0,
+			thumb_width: 0.0,      //calculate dynamically
+			thumb_height: 0.0,     //calculate dynamically
+			thumb_rel_offset: 0.0, //calculate dynamically
+
+			scroll_perc: 0.0, //calculate dynamically
+		},
+		page_scroll: None,
+		search_text: String::new(),
+		density: Vec::new(),
+		density_block_size: 1,
+		severity_index: Default::default(),
+		source_index: std::collections::HashMap::new(),
+		search_cache: Vec::new(),
+		search_job: None,
+		visible_offsets: Vec::new(),
+
+		wrap: false,
+		wrap_chars: 0, //calculate dynamically
+		h_offset: 0.0,
+		content_width: 0.0, //calculate dynamically
+		h_thumb_drag: false,
+		h_scroll_bar: ScrollBarHorz {
+			x: 0.0, //calculate dynamically
+			y: 0.0, //calculate dynamically
+
+			bar_padding: 10.0,
+			bar_width: 0.0, //calculate dynamically
+			bar_height: 20.0,
+
+			thumb_x: 0.0, //calculate dynamically
+			thumb_y: 0.0, //calculate dynamically
+			thumb_margin: 3.0,
+			thumb_width: 50.0,
+			thumb_height: 0.0,     //calculate dynamically
 			thumb_rel_offset: 0.0, //calculate dynamically
 
 			scroll_perc: 0.0, //calculate dynamically
 		},
 	};
 
+	settings.apply(&mut store, preferred_font);
 	let store_rc = Rc::new(RefCell::new(store));
+	let settings_rc = Rc::new(RefCell::new(settings));
 
 	//---------------------------------------------------------------------------------------
 
 	let drawing_area = DrawingArea::new();
 
-	let mut log_source_root_ext = LogSourceExt::from_source(log_source_root);
+	let mut log_source_root_ext = log_source_root;
+	let messages = messages.finish();
+	log::info!(
+		"{} distinct messages, {} KiB",
+		messages.len(),
+		messages.heap_size() / 1024
+	);
+	store_rc.borrow_mut().messages = std::sync::Arc::new(messages);
 
 	// left pane
 	let left_store = TreeStore::new(&[
@@ -931,6 +1887,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		String::static_type(),
 		glib::Type::U32,
 		glib::Type::U64,
+		String::static_type(),
 	]);
 	//let left_store_sort = gtk::TreeModelSort::new(&left_store);
 	//Do not use TreeModelSort:
@@ -959,6 +1916,9 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		);
 	}*/
 
+	// Column with the color of a log source, created here as the row activation handler needs it
+	let color_column = gtk::TreeViewColumn::new();
+
 	// Column with checkbox to toggle log sources, plus log source name
 	{
 		let column = gtk::TreeViewColumn::new();
@@ -1013,13 +1973,24 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 										   //let model_sort_clone = left_store_sort.clone(); //GTK objects are refcounted, just clones ref
 			let drawing_area_clone = drawing_area.clone(); //GTK objects are refcounted, just clones ref
 			let store_rc_clone = store_rc.clone();
-			sources_tree_view.connect_row_activated(move |_tree_view, path, _column| {
-				toggle_row(
-					&left_store_clone,
-					&mut store_rc_clone.borrow_mut(),
-					&drawing_area_clone,
-					path.clone(),
-				)
+			let color_column_clone = color_column.clone();
+			sources_tree_view.connect_row_activated(move |tree_view, path, column| {
+				if column == &color_column_clone {
+					choose_source_color(
+						tree_view,
+						&left_store_clone,
+						&store_rc_clone,
+						&drawing_area_clone,
+						path,
+					)
+				} else {
+					toggle_row(
+						&left_store_clone,
+						&mut store_rc_clone.borrow_mut(),
+						&drawing_area_clone,
+						path.clone(),
+					)
+				}
 			});
 		}
 	}
@@ -1046,11 +2017,37 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		sources_tree_view.append_column(&column);
 	}
 
+	//Column with the color of a log source, activate to change it
+	{
+		color_column.set_title("Color");
+
+		{
+			let renderer_text = CellRendererText::new();
+			gtk::prelude::TreeViewColumnExt::pack_start(&color_column, &renderer_text, false);
+			renderer_text.set_text(Some("    "));
+			gtk::prelude::TreeViewColumnExt::add_attribute(
+				&color_column,
+				&renderer_text,
+				"background",
+				LogSourcesColumns::Color as i32,
+			);
+		}
+		sources_tree_view.append_column(&color_column);
+	}
+
 	fn build_left_store(
 		store: &TreeStore,
 		log_source: &LogSourceExt,
 		parent: Option<&gtk::TreeIter>,
+		log_store: &LogStoreLinear,
 	) {
+		//Only log sources that contain entries get a color
+		let color = match &log_source.children {
+			LogSourceContentsExt::Sources(_) => None,
+			LogSourceContentsExt::Entries(_) => Some(log_store::color_to_hex(
+				log_store.source_color(log_source.id),
+			)),
+		};
 		let new_parent = store.insert_with_values(
 			parent,
 			None,
@@ -1060,18 +2057,19 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 				(LogSourcesColumns::Text as u32, &log_source.name),
 				(LogSourcesColumns::Id as u32, &log_source.id),
 				(LogSourcesColumns::ChildCount as u32, &log_source.child_cnt),
+				(LogSourcesColumns::Color as u32, &color),
 			],
 		);
 		match &log_source.children {
 			LogSourceContentsExt::Sources(v) => {
 				for source in v {
-					build_left_store(store, source, Some(&new_parent));
+					build_left_store(store, source, Some(&new_parent), log_store);
 				}
 			}
 			LogSourceContentsExt::Entries(_v) => (),
 		}
 	}
-	build_left_store(&left_store, &log_source_root_ext, None);
+	build_left_store(&left_store, &log_source_root_ext, None, &store_rc.borrow());
 	sources_tree_view.expand_row(&gtk::TreePath::new_first(), false);
 	//sources_tree_view.expand_all();
 
@@ -1093,15 +2091,20 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		drawing_area: &gtk::DrawingArea,
 	) {
 		log::info!("Active: {} ({:?})", w.is_active(), severity);
-		store.filter_store(
-			&|entry: &LogEntryExt| entry.severity == severity,
-			w.is_active(),
-			crate::model_internal::VISIBLE_OFF_SEVERITY,
-		);
+		match &severity {
+			model::LogLevel::Critical => store.show_crit = w.is_active(),
+			model::LogLevel::Error => store.show_err = w.is_active(),
+			model::LogLevel::Warning => store.show_warn = w.is_active(),
+			model::LogLevel::Info => store.show_info = w.is_active(),
+			model::LogLevel::Debug => store.show_dbg = w.is_active(),
+			model::LogLevel::Trace => store.show_trace = w.is_active(),
+		}
+		store.set_severity_visible(&severity, w.is_active());
 		drawing_area.queue_draw();
 	}
 
 	split_pane_left.pack_start(&scrolled_window_left, true, true, 0);
+	let mut severity_btns = Vec::<gtk::CheckButton>::new(); //Critical to Trace
 	{
 		let check_btn = gtk::CheckButton::with_label("Critical");
 		check_btn.set_active(true);
@@ -1118,6 +2121,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		split_pane_left.pack_start(&check_btn, false, false, 0);
+		severity_btns.push(check_btn.clone());
 		let check_btn = gtk::CheckButton::with_label("Error");
 		check_btn.set_active(true);
 
@@ -1133,6 +2137,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		split_pane_left.pack_start(&check_btn, false, false, 0);
+		severity_btns.push(check_btn.clone());
 		let check_btn = gtk::CheckButton::with_label("Warning");
 		check_btn.set_active(true);
 
@@ -1148,6 +2153,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		split_pane_left.pack_start(&check_btn, false, false, 0);
+		severity_btns.push(check_btn.clone());
 		let check_btn = gtk::CheckButton::with_label("Info");
 		check_btn.set_active(true);
 
@@ -1163,6 +2169,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		split_pane_left.pack_start(&check_btn, false, false, 0);
+		severity_btns.push(check_btn.clone());
 		let check_btn = gtk::CheckButton::with_label("Debug");
 		check_btn.set_active(true);
 
@@ -1178,6 +2185,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		split_pane_left.pack_start(&check_btn, false, false, 0);
+		severity_btns.push(check_btn.clone());
 		let check_btn = gtk::CheckButton::with_label("Trace");
 		check_btn.set_active(true);
 
@@ -1193,45 +2201,103 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		split_pane_left.pack_start(&check_btn, false, false, 0);
+		severity_btns.push(check_btn.clone());
+	}
+
+	{
+		let check_btn = gtk::CheckButton::with_label("Wrap long lines");
+		check_btn.set_active(false);
+
+		let store_rc_clone = store_rc.clone();
+		let drawing_area_clone = drawing_area.clone();
+		check_btn.connect_clicked(move |w| {
+			let mut store = store_rc_clone.borrow_mut();
+			store.wrap = w.is_active();
+			//Row count per entry is recalculated on the next draw
+			store.wrap_chars = 0;
+			store.viewport_row = 0;
+			store.h_offset = 0.0;
+			drawing_area_clone.queue_draw();
+		});
+
+		split_pane_left.pack_start(&check_btn, false, false, 0);
+	}
+
+	{
+		let preferences_btn = gtk::Button::with_label("Preferences…");
+		let settings_rc_clone = settings_rc.clone();
+		let store_rc_clone = store_rc.clone();
+		let drawing_area_clone = drawing_area.clone();
+		preferences_btn.connect_clicked(move |w| {
+			show_preferences_dialog(
+				w,
+				&settings_rc_clone,
+				&store_rc_clone,
+				&drawing_area_clone,
+				preferred_font,
+			);
+		});
+		split_pane_left.pack_start(&preferences_btn, false, false, 0);
+
+		//Follow changes of the desktop theme while the theme kind is `System`
+		if let Some(settings) = gtk::Settings::default() {
+			let store_rc_clone = store_rc.clone();
+			let drawing_area_clone = drawing_area.clone();
+			let system_theme_changed = move || {
+				let mut store = store_rc_clone.borrow_mut();
+				store.system_dark = system_prefers_dark();
+				store.update_theme();
+				drawing_area_clone.queue_draw();
+			};
+			let system_theme_changed_clone = system_theme_changed.clone();
+			settings.connect_notify_local(Some("gtk-theme-name"), move |_, _| {
+				system_theme_changed_clone()
+			});
+			settings
+				.connect_notify_local(Some("gtk-application-prefer-dark-theme"), move |_, _| {
+					system_theme_changed()
+				});
+		}
 	}
 
 	fn search_changed(
 		w: &gtk::SearchEntry,
-		store: &mut LogStoreLinear,
+		store_rc: &Rc<RefCell<LogStoreLinear>>,
 		drawing_area: &gtk::DrawingArea,
+		searching_label: &gtk::Label,
 	) {
 		let search_text = w.text().as_str().to_string();
-		if search_text.is_empty() {
-			log::info!("Search empty");
-			store.filter_store(
-				&|_entry: &LogEntryExt| true,
-				true,
-				crate::model_internal::VISIBLE_OFF_FILTER,
-			);
+		log::info!("search_changed {}", &search_text);
+		let was_searching = store_rc.borrow().search_job.is_some();
+		if store_rc.borrow_mut().start_search(&search_text) {
+			//A running watch picks up the new search
+			if !was_searching {
+				watch_search(store_rc, drawing_area, searching_label);
+			}
 		} else {
-			log::info!("search_changed {}", &search_text);
-			store.filter_store(
-				&|entry: &LogEntryExt| entry.message.contains(&search_text),
-				true,
-				crate::model_internal::VISIBLE_OFF_FILTER,
-			);
-			store.filter_store(
-				&|entry: &LogEntryExt| !entry.message.contains(&search_text),
-				false,
-				crate::model_internal::VISIBLE_OFF_FILTER,
-			);
+			searching_label.hide();
 		}
 		drawing_area.queue_draw();
 	}
 
 	let search_entry = gtk::SearchEntry::new();
+	let searching_label = gtk::Label::new(None);
+	searching_label.set_xalign(0.0);
+	searching_label.set_no_show_all(true);
 	let store_rc_clone = store_rc.clone();
 	let drawing_area_clone = drawing_area.clone();
+	let searching_label_clone = searching_label.clone();
 	search_entry.connect_search_changed(move |w| {
-		search_changed(w, &mut store_rc_clone.borrow_mut(), &drawing_area_clone);
+		search_changed(
+			w,
+			&store_rc_clone,
+			&drawing_area_clone,
+			&searching_label_clone,
+		);
 	});
 
 	split_pane_left.pack_start(&search_entry, false, false, 0);
+	split_pane_left.pack_start(&searching_label, false, false, 0);
 
 	let timediff_entry = gtk::Entry::new();
 	timediff_entry.set_editable(false);
@@ -1243,29 +2309,152 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	timediff_box.pack_start(&timediff_entry, true, true, 0);
 	split_pane_left.pack_start(&timediff_box, false, false, 0);
 
+	let time_start_entry = gtk::Entry::new();
+	time_start_entry.set_placeholder_text(Some("Start, e.g. 14:03"));
+	let time_end_entry = gtk::Entry::new();
+	time_end_entry.set_placeholder_text(Some("End, e.g. 14:07"));
+	for entry in [&time_start_entry, &time_end_entry] {
+		entry.set_tooltip_text(Some(
+			"Timestamp as shown in the log, YYYY-MM-DD hh:mm[:ss] or hh:mm[:ss] on the anchor's day",
+		));
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		let time_start_entry = time_start_entry.clone();
+		let time_end_entry = time_end_entry.clone();
+		entry.connect_activate(move |_entry| {
+			apply_time_range_entries(
+				&time_start_entry,
+				&time_end_entry,
+				&mut store_rc.borrow_mut(),
+				&drawing_area,
+			);
+		});
+	}
+	let time_range_box = gtk::Box::new(Orientation::Horizontal, 4);
+	time_range_box.pack_start(&gtk::Label::new(Some("Time:")), false, false, 0);
+	time_range_box.pack_start(&time_start_entry, true, true, 0);
+	time_range_box.pack_start(&gtk::Label::new(Some("–")), false, false, 0);
+	time_range_box.pack_start(&time_end_entry, true, true, 0);
+	split_pane_left.pack_start(&time_range_box, false, false, 0);
+
+	let time_buttons_box = gtk::Box::new(Orientation::Horizontal, 4);
+	let time_from_selection_btn = gtk::Button::with_label("From anchor and selection");
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		let time_start_entry = time_start_entry.clone();
+		let time_end_entry = time_end_entry.clone();
+		time_from_selection_btn.connect_clicked(move |_btn| {
+			let mut store = store_rc.borrow_mut();
+			let timestamps = store
+				.selected_offsets()
+				.into_iter()
+				.chain(store.anchor_offset)
+				.map(|offset| store.store[offset].datetime())
+				.collect::<Vec<_>>();
+			if let (Some(start), Some(end)) = (timestamps.iter().min(), timestamps.iter().max()) {
+				let (start, end) = (*start, *end);
+				store.set_time_range(Some((start, end)));
+				show_time_range(&time_start_entry, &time_end_entry, &store);
+				drawing_area.queue_draw();
+			}
+		});
+	}
+	time_buttons_box.pack_start(&time_from_selection_btn, true, true, 0);
+	let time_clear_btn = gtk::Button::with_label("Clear");
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		let time_start_entry = time_start_entry.clone();
+		let time_end_entry = time_end_entry.clone();
+		time_clear_btn.connect_clicked(move |_btn| {
+			let mut store = store_rc.borrow_mut();
+			store.set_time_range(None);
+			show_time_range(&time_start_entry, &time_end_entry, &store);
+			drawing_area.queue_draw();
+		});
+	}
+	time_buttons_box.pack_start(&time_clear_btn, false, false, 0);
+	split_pane_left.pack_start(&time_buttons_box, false, false, 0);
+
+	let sort_combo = gtk::ComboBoxText::new();
+	for sort_mode in log_store::SortMode::ALL {
+		sort_combo.append(Some(sort_mode.id()), sort_mode.label());
+	}
+	sort_combo.set_active_id(Some(store_rc.borrow().sort_mode.id()));
+	//Shown once the store is assembled if there are boot-relative entries
+	let place_boot_relative_check = gtk::CheckButton::new();
+	place_boot_relative_check.set_tooltip_text(Some(
+		"Entries without EtherCAT time only know the uptime of their device. Sort them after the last entry of the same session instead of in 1970.",
+	));
+	place_boot_relative_check.set_no_show_all(true);
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		let place_boot_relative_check = place_boot_relative_check.clone();
+		sort_combo.connect_changed(move |combo| {
+			let sort_mode = combo
+				.active_id()
+				.and_then(|id| log_store::SortMode::from_id(id.as_str()));
+			if let Some(sort_mode) = sort_mode {
+				store_rc.borrow_mut().set_sort_mode(sort_mode);
+				place_boot_relative_check
+					.set_sensitive(sort_mode == log_store::SortMode::Timestamp);
+				drawing_area.queue_draw();
+			}
+		});
+	}
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		place_boot_relative_check.connect_toggled(move |check| {
+			store_rc
+				.borrow_mut()
+				.set_place_boot_relative(check.is_active());
+			drawing_area.queue_draw();
+		});
+	}
+	let sort_box = gtk::Box::new(Orientation::Horizontal, 4);
+	sort_box.pack_start(&gtk::Label::new(Some("Sort by:")), false, false, 0);
+	sort_box.pack_start(&sort_combo, true, true, 0);
+	split_pane_left.pack_start(&sort_box, false, false, 0);
+	split_pane_left.pack_start(&place_boot_relative_check, false, false, 0);
+	//Shown once the store is assembled if any timestamps were corrected
+	let corrections_check = gtk::CheckButton::with_label("Apply timestamp corrections");
+	corrections_check.set_active(true);
+	corrections_check.set_no_show_all(true);
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		corrections_check.connect_toggled(move |check| {
+			store_rc
+				.borrow_mut()
+				.set_corrections_enabled(check.is_active());
+			drawing_area.queue_draw();
+		});
+	}
+	split_pane_left.pack_start(&corrections_check, false, false, 0);
+
 	split_pane.pack1(&split_pane_left, false, false);
 
 	//https://developer.gnome.org/gtk3/stable/GtkPaned.html
 
 	// Assemble log store ----------------------------------------------------------
 
-	fn build_log_store(store: &mut Vec<LogEntryExt>, log_source: &mut LogSourceExt) {
+	//Entries of each source, in the order of the tree
+	fn build_log_store(sources: &mut Vec<Vec<LogEntryExt>>, log_source: &mut LogSourceExt) {
 		match &mut log_source.children {
 			LogSourceContentsExt::Sources(v) => {
 				for source in v {
-					build_log_store(store, source);
+					build_log_store(sources, source);
 				}
 			}
 			LogSourceContentsExt::Entries(v) => {
-				store.append(v);
+				sources.push(std::mem::take(v));
 			}
 		}
 	}
 
-	log::info!("before build_log_store");
-	let now = Instant::now();
-	build_log_store(&mut store_rc.borrow_mut().store, &mut log_source_root_ext);
-
 	fn build_log_sources(
 		log_sources: &mut std::collections::HashMap<u32, String>,
 		log_source: &LogSourceExt,
@@ -1289,15 +2478,59 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		String::new(),
 	);
 
-	store_rc
-		.borrow_mut()
-		.store
-		.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-	store_rc.borrow_mut().filter_store(
-		&|_entry: &LogEntryExt| true,
-		true,
-		crate::model_internal::VISIBLE_OFF_SOURCE,
-	); //set all to active, initialize ids
+	log::info!("before build_log_store");
+	let now = Instant::now();
+	let mut sources = Vec::new();
+	build_log_store(&mut sources, &mut log_source_root_ext);
+	{
+		let mut store = store_rc.borrow_mut();
+		let rules = settings_rc
+			.borrow()
+			.correction_rules(&sfile_layout.corrected_categories());
+		let corrections =
+			correction::find_corrections(&rules, &sources, &store.log_sources, &store.messages);
+		correction::apply(&mut sources, &corrections.deltas, 1);
+		let report: Vec<String> = rules
+			.iter()
+			.zip(&corrections.counts)
+			.map(|(rule, count)| format!("{}: {} entries", rule.name, count))
+			.collect();
+		log::info!("Timestamp corrections: {}", report.join(", "));
+		corrections_check.set_tooltip_text(Some(&format!(
+			"Uncheck to compare with the raw timestamps of the log files.\n{}",
+			report.join("\n")
+		)));
+		corrections_check.set_visible(!corrections.deltas.is_empty());
+		store.corrections = corrections.deltas;
+		(store.store, store.file_positions) = log_store::merge_sources(sources);
+		//Sensor logs whose device never got its EtherCAT time, see `correction`
+		let boot_relative_count = store
+			.store
+			.iter()
+			.filter(|entry| entry.timestamp.is_boot_relative())
+			.count();
+		place_boot_relative_check.set_label(&format!(
+			"Place {} boot-relative entries after their session",
+			boot_relative_count
+		));
+		place_boot_relative_check.set_visible(boot_relative_count > 0);
+	}
+
+	store_rc.borrow_mut().build_indices(); //initialize ids and links
+
+	{
+		//Every custom field that occurs in the log can be shown as a column
+		let mut store = store_rc.borrow_mut();
+		let custom_fields = store
+			.store
+			.iter()
+			.flat_map(|entry| entry.custom_fields.iter().map(|(name, _)| name.as_ref()))
+			.collect::<std::collections::BTreeSet<&str>>()
+			.into_iter()
+			.map(|name| name.to_string())
+			.collect::<Vec<String>>();
+		store.columns = columns::default_columns(&custom_fields);
+	}
 
 	let elapsed = now.elapsed();
 	log::info!(
@@ -1325,15 +2558,51 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	// establish a reasonable minimum view size
 	drawing_area.set_size_request(200, 200);
 
+	//Tooltip with the full log source path when hovering the color stripe or the source column
+	drawing_area.set_has_tooltip(true);
+	let f_clone_0 = store_rc.clone();
+	drawing_area.connect_query_tooltip(move |_drawing_area, x, y, _keyboard_mode, tooltip| {
+		let store = f_clone_0.borrow();
+		let x = x as f64;
+		let y = y as f64;
+		let on_stripe = x >= store.border_left - 6.0 && x < store.border_left;
+		let on_source_column = store
+			.column_at(x)
+			.is_some_and(|idx| store.columns[idx].kind == columns::ColumnKind::Source);
+		if !(on_stripe || on_source_column) {
+			return false;
+		}
+		let line = if let Some(line) = store.line_at(y) {
+			line
+		} else {
+			return false;
+		};
+		if let Some(offset) = store.rel_to_abs_offset(line) {
+			if let Some(path) = store.log_sources.get(&store.store[offset].source_id) {
+				tooltip.set_text(Some(path));
+				return true;
+			}
+		}
+		false
+	});
+
 	// https://gtk-rs.org/docs/gtk/trait.WidgetExt.html
+	let timeline_area = DrawingArea::new();
+	timeline_area.set_size_request(200, TIMELINE_HEIGHT);
+
 	let f_clone_2 = store_rc.clone();
+	let timeline_area_clone = timeline_area.clone();
+	let shown_time_range = std::cell::Cell::new(None);
 	drawing_area.connect_draw(move |drawing_area, ctx| {
-		draw(
-			&mut f_clone_2.borrow_mut(),
-			drawing_area,
-			ctx,
-			preferred_font,
-		)
+		//Viewport and anchor markers of the timeline follow the log view
+		timeline_area_clone.queue_draw();
+		let mut store = f_clone_2.borrow_mut();
+		//The range may also be set from the timeline or context menu; leave typing alone otherwise
+		if store.time_range != shown_time_range.get() {
+			shown_time_range.set(store.time_range);
+			show_time_range(&time_start_entry, &time_end_entry, &store);
+		}
+		draw(&mut store, drawing_area, ctx)
 	});
 
 	let f_clone_3 = store_rc.clone();
@@ -1342,8 +2611,50 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	});
 
 	let f_clone_4 = store_rc.clone();
+	let left_store_clone = left_store.clone();
+	let search_entry_clone = search_entry.clone();
 	drawing_area.connect_button_press_event(move |drawing_area, evt| {
-		handle_evt_press(&mut f_clone_4.borrow_mut(), drawing_area, evt)
+		let in_header = {
+			let store = f_clone_4.borrow();
+			evt.position().1 >= store.border_top
+				&& evt.position().1 < store.border_top + store.header_height
+		};
+		if in_header && evt.button() == 3 {
+			show_column_menu(&f_clone_4, drawing_area, evt);
+			return gtk::Inhibit(true);
+		}
+		if evt.button() == 3 {
+			//Right click only opens the context menu, it does not move anchor or selection
+			if evt.event_type() != gdk::EventType::ButtonPress {
+				return gtk::Inhibit(true);
+			}
+			let clicked_entry = {
+				let store = f_clone_4.borrow();
+				if evt.position().0 >= store.border_left && evt.position().0 < store.scroll_bar.x {
+					store
+						.line_at(evt.position().1)
+						.and_then(|line| store.rel_to_abs_offset(line))
+				} else {
+					None
+				}
+			};
+			if let Some(offset) = clicked_entry {
+				show_entry_menu(
+					&f_clone_4,
+					&left_store_clone,
+					&search_entry_clone,
+					drawing_area,
+					evt,
+					offset,
+				);
+			}
+			return gtk::Inhibit(true);
+		}
+		let inhibit = handle_evt_press(&mut f_clone_4.borrow_mut(), drawing_area, evt);
+		if f_clone_4.borrow().page_scroll.is_some() {
+			start_page_repeat(&f_clone_4, drawing_area);
+		}
+		inhibit
 	});
 
 	let f_clone_5 = store_rc.clone();
@@ -1361,7 +2672,50 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		)
 	});
 
-	split_pane.pack2(&drawing_area, true, false);
+	timeline_area.add_events(
+		EventMask::POINTER_MOTION_MASK
+			| EventMask::BUTTON_PRESS_MASK
+			| EventMask::BUTTON_RELEASE_MASK,
+	);
+	{
+		let store_rc = store_rc.clone();
+		timeline_area.connect_draw(move |timeline_area, ctx| {
+			draw_timeline(&mut store_rc.borrow_mut(), timeline_area, ctx)
+		});
+	}
+	{
+		let store_rc = store_rc.clone();
+		timeline_area.connect_button_press_event(move |_timeline_area, evt| {
+			if evt.button() == 1 && evt.event_type() == gdk::EventType::ButtonPress {
+				let x = evt.position().0;
+				store_rc.borrow_mut().timeline_brush = Some((x, x));
+			}
+			gtk::Inhibit(true)
+		});
+	}
+	{
+		let store_rc = store_rc.clone();
+		timeline_area.connect_motion_notify_event(move |timeline_area, evt| {
+			if let Some((_, x)) = &mut store_rc.borrow_mut().timeline_brush {
+				*x = evt.position().0;
+				timeline_area.queue_draw();
+			}
+			gtk::Inhibit(false)
+		});
+	}
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		timeline_area.connect_button_release_event(move |timeline_area, _evt| {
+			handle_timeline_release(&mut store_rc.borrow_mut(), timeline_area, &drawing_area);
+			gtk::Inhibit(true)
+		});
+	}
+
+	let log_view_box = gtk::Box::new(Orientation::Vertical, 0);
+	log_view_box.pack_start(&timeline_area, false, false, 0);
+	log_view_box.pack_start(&drawing_area, true, true, 0);
+	split_pane.pack2(&log_view_box, true, false);
 
 	//https://gtk-rs.org/docs/gdk/enums/key/index.html
 	//log::info!("CODES: {} {} {} {}", gdk::keys::constants::Control_L, gdk::keys::constants::Control_R, gdk::keys::constants::Shift_L, gdk::keys::constants::Shift_R);
@@ -1369,6 +2723,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	https://stackoverflow.com/questions/52171141/gtkmm-how-to-attach-keyboard-events-to-an-drawingarea*/
 	{
 		let store_rc_clone = store_rc.clone();
+		let drawing_area_clone = drawing_area.clone();
 		window.connect_key_press_event(move |_window, event_key| {
 			log::info!(
 				"KEY PRESSED! {} {}",
@@ -1385,36 +2740,53 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 			{
 				store_rc_clone.borrow_mut().pressed_shift = true;
 			}
-			if event_key.keyval() == gdk::keys::constants::c && store_rc_clone.borrow().pressed_ctrl
+			//Ctrl+C: Selection as text, Ctrl+Shift+C: Selection as Markdown table
+			if (event_key.keyval() == gdk::keys::constants::c
+				|| event_key.keyval() == gdk::keys::constants::C)
+				&& store_rc_clone.borrow().pressed_ctrl
 			{
-				let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
-
-				//TODO: 13.04.2020: Can optimize this, use some sort of string stream
-				let mut clip_string = std::string::String::new();
-
-				//TODO: 13.04.2020: Can optimize this, do not go through entire store
-				for (offset, entry) in store_rc_clone
-					.borrow()
-					.store
-					.iter()
-					.enumerate() //offset in vector
-					.filter(|(_, x)| x.is_visible())
-				{
-					//TODO: 13.04.2020: Clean up all these borrows.
-					if (store_rc_clone.borrow().selected_single.contains(&offset)
-						|| (store_rc_clone.borrow().selected_range.is_some()
-							&& store_rc_clone.borrow().selected_range.unwrap().0 <= offset
-							&& store_rc_clone.borrow().selected_range.unwrap().1 >= offset))
-						&& !store_rc_clone.borrow().excluded_single.contains(&offset)
-					{
-						//TODO: 13.04.2020: Also add log source name to string!
-						clip_string += &entry.timestamp.format("%d-%m-%y %T%.6f").to_string();
-						clip_string += &" | ";
-						clip_string += &entry.message;
-						clip_string += &"\r\n"; //TODO: 13.04.2020: Windows vs Linux file endings?
+				let store = store_rc_clone.borrow();
+				let offsets = store.selected_offsets();
+				let clip_string = if store.pressed_shift {
+					store.copy_format.format_markdown(&store, &offsets)
+				} else {
+					store.copy_format.format_entries(&store, &offsets)
+				};
+				gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&clip_string);
+			}
+			if event_key.keyval() == gdk::keys::constants::b && store_rc_clone.borrow().pressed_ctrl
+			{
+				let mut store = store_rc_clone.borrow_mut();
+				if let Some(anchor_offset) = store.anchor_offset {
+					if !store.bookmarks.insert(anchor_offset) {
+						store.bookmarks.remove(&anchor_offset);
 					}
+					drawing_area_clone.queue_draw();
+				}
+			}
+			if event_key.keyval() == gdk::keys::constants::F2 {
+				//Jump to the next (or with shift, the previous) visible bookmark
+				let mut store = store_rc_clone.borrow_mut();
+				let current = store.anchor_offset.unwrap_or(store.viewport_offset);
+				let target = if store.pressed_shift {
+					store
+						.bookmarks
+						.range(..current)
+						.rev()
+						.find(|offset| store.store[**offset].is_visible())
+						.copied()
+				} else {
+					store
+						.bookmarks
+						.range(current + 1..)
+						.find(|offset| store.store[**offset].is_visible())
+						.copied()
+				};
+				if let Some(target) = target {
+					store.anchor_offset = Some(target);
+					store.jump_to(target);
+					drawing_area_clone.queue_draw();
 				}
-				clipboard.set_text(&clip_string);
 			}
 			gtk::Inhibit(false)
 		});
@@ -1442,6 +2814,57 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 	}
 
+	let default_visibility = settings_rc.borrow().severity_visibility();
+	for (check_btn, show) in severity_btns.iter().zip(default_visibility) {
+		check_btn.set_active(show);
+	}
+
+	if let Some(session_path) = session_path {
+		if session_path.exists() {
+			match session::Session::load(&session_path, default_visibility) {
+				Ok(session) => {
+					log::info!("Restoring session from {:?}", session_path);
+					//Reorders the store, so before the anchor is restored. The widgets are
+					//updated afterwards, their handlers find the store in the right order then.
+					store_rc.borrow_mut().restore_order(
+						session.sort_mode,
+						session.place_boot_relative,
+						session.corrections_enabled,
+					);
+					sort_combo.set_active_id(Some(session.sort_mode.id()));
+					place_boot_relative_check.set_active(session.place_boot_relative);
+					corrections_check.set_active(session.corrections_enabled);
+					restore_session(
+						&session,
+						&left_store,
+						&store_rc,
+						&drawing_area,
+						&search_entry,
+						&severity_btns,
+					);
+				}
+				Err(err) => log::warn!("Could not load session {:?}: {}", session_path, err),
+			}
+		}
+
+		let store_rc_clone = store_rc.clone();
+		window.connect_delete_event(move |_window, _evt| {
+			let store = store_rc_clone.borrow();
+			let session = session::Session::capture(
+				&store,
+				hidden_sources(&left_store, &store.log_sources),
+				search_entry.text().as_str().to_string(),
+			);
+			//Do not litter the file system with sessions that contain nothing of interest
+			if !session.is_default(default_visibility) || session_path.exists() {
+				if let Err(err) = session.save(&session_path) {
+					log::warn!("Could not save session {:?}: {}", session_path, err);
+				}
+			}
+			gtk::Inhibit(false)
+		});
+	}
+
 	window.add(&split_pane);
 	window.show_all();
 
@@ -1451,6 +2874,898 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	}
 }
 
+//Returns the topmost hidden log sources; their children are implicitly hidden as well
+fn hidden_sources(
+	tree_store: &gtk::TreeStore,
+	log_sources: &std::collections::HashMap<u32, String>,
+) -> Vec<String> {
+	fn collect_hidden(
+		tree_store: &gtk::TreeStore,
+		parent: Option<&gtk::TreeIter>,
+		log_sources: &std::collections::HashMap<u32, String>,
+		hidden: &mut Vec<String>,
+	) {
+		if let Some(iter) = tree_store.iter_children(parent) {
+			loop {
+				let active = tree_store
+					.value(&iter, LogSourcesColumns::Active as i32)
+					.get::<bool>()
+					.unwrap();
+				let inconsistent = tree_store
+					.value(&iter, LogSourcesColumns::Inconsistent as i32)
+					.get::<bool>()
+					.unwrap();
+				if !active && !inconsistent {
+					let id = tree_store
+						.value(&iter, LogSourcesColumns::Id as i32)
+						.get::<u32>()
+						.unwrap();
+					if let Some(path) = log_sources.get(&id) {
+						hidden.push(session::source_key(path).to_string());
+					}
+				} else {
+					collect_hidden(tree_store, Some(&iter), log_sources, hidden);
+				}
+				if !tree_store.iter_next(&iter) {
+					break;
+				}
+			}
+		}
+	}
+	let mut hidden = Vec::new();
+	collect_hidden(tree_store, None, log_sources, &mut hidden);
+	hidden
+}
+
+fn restore_session(
+	session: &session::Session,
+	tree_store: &gtk::TreeStore,
+	store_rc: &Rc<RefCell<LogStoreLinear>>,
+	drawing_area: &gtk::DrawingArea,
+	search_entry: &gtk::SearchEntry,
+	severity_btns: &[gtk::CheckButton],
+) {
+	//Note: The GTK signal handlers triggered below borrow the store themselves
+
+	let show_severities = [
+		session.show_crit,
+		session.show_err,
+		session.show_warn,
+		session.show_info,
+		session.show_dbg,
+		session.show_trace,
+	];
+	for (check_btn, show) in severity_btns.iter().zip(show_severities) {
+		check_btn.set_active(show);
+	}
+
+	fn find_hidden(
+		tree_store: &gtk::TreeStore,
+		parent: Option<&gtk::TreeIter>,
+		log_sources: &std::collections::HashMap<u32, String>,
+		hidden: &std::collections::HashSet<&str>,
+		paths: &mut Vec<gtk::TreePath>,
+	) {
+		if let Some(iter) = tree_store.iter_children(parent) {
+			loop {
+				let id = tree_store
+					.value(&iter, LogSourcesColumns::Id as i32)
+					.get::<u32>()
+					.unwrap();
+				let is_hidden = log_sources
+					.get(&id)
+					.is_some_and(|path| hidden.contains(session::source_key(path)));
+				if is_hidden {
+					paths.extend(tree_store.path(&iter));
+				} else {
+					find_hidden(tree_store, Some(&iter), log_sources, hidden, paths);
+				}
+				if !tree_store.iter_next(&iter) {
+					break;
+				}
+			}
+		}
+	}
+	let hidden = session
+		.hidden_sources
+		.iter()
+		.map(|source| source.as_str())
+		.collect::<std::collections::HashSet<&str>>();
+	let mut paths = Vec::new();
+	find_hidden(
+		tree_store,
+		None,
+		&store_rc.borrow().log_sources,
+		&hidden,
+		&mut paths,
+	);
+	for path in paths {
+		toggle_row(tree_store, &mut store_rc.borrow_mut(), drawing_area, path);
+	}
+
+	{
+		let mut store = store_rc.borrow_mut();
+		let ids = store
+			.log_sources
+			.iter()
+			.map(|(id, path)| (session::source_key(path).to_string(), *id))
+			.collect::<std::collections::HashMap<String, u32>>();
+		for (source, color) in &session.source_colors {
+			if let Some(id) = ids.get(source) {
+				store.source_colors.insert(*id, *color);
+			}
+		}
+	}
+	refresh_source_colors(tree_store, &store_rc.borrow());
+
+	{
+		let mut store = store_rc.borrow_mut();
+		if session.field_filter.is_some() {
+			store.set_field_filter(session.field_filter.clone());
+		}
+		if session.time_range.is_some() {
+			store.set_time_range(session.time_range);
+		}
+		let offsets = session::resolve_entries(&store, &session.bookmarks);
+		let missing = offsets.iter().filter(|offset| offset.is_none()).count();
+		if missing > 0 {
+			log::warn!("Could not find {} bookmarked entries of session", missing);
+		}
+		store.bookmarks = offsets.into_iter().flatten().collect();
+
+		if let Some(anchor) = &session.anchor {
+			if let Some(anchor_offset) =
+				session::resolve_entries(&store, std::slice::from_ref(anchor))[0]
+			{
+				store.anchor_offset = Some(anchor_offset);
+				store.jump_to(anchor_offset);
+			} else {
+				log::warn!("Could not find anchor entry of session");
+			}
+		}
+	}
+
+	//Applied asynchronously by the search entry, keeping the anchor in place
+	search_entry.set_text(&session.search_text);
+}
+
+//Context menu of the column header to show and hide columns
+fn show_column_menu(
+	store_rc: &Rc<RefCell<LogStoreLinear>>,
+	drawing_area: &gtk::DrawingArea,
+	evt: &gdk::EventButton,
+) {
+	let menu = gtk::Menu::new();
+	for (idx, column) in store_rc.borrow().columns.iter().enumerate() {
+		let item = gtk::CheckMenuItem::with_label(column.title());
+		item.set_active(column.visible);
+		//The message column is always shown
+		item.set_sensitive(column.kind != columns::ColumnKind::Message);
+		let store_rc_clone = store_rc.clone();
+		let drawing_area_clone = drawing_area.clone();
+		item.connect_toggled(move |item| {
+			store_rc_clone.borrow_mut().columns[idx].visible = item.is_active();
+			drawing_area_clone.queue_draw();
+		});
+		menu.append(&item);
+	}
+	menu.append(&gtk::SeparatorMenuItem::new());
+	let item = gtk::MenuItem::with_label("Reset column widths");
+	let store_rc_clone = store_rc.clone();
+	let drawing_area_clone = drawing_area.clone();
+	item.connect_activate(move |_item| {
+		for column in store_rc_clone.borrow_mut().columns.iter_mut() {
+			column.user_width = None;
+		}
+		drawing_area_clone.queue_draw();
+	});
+	menu.append(&item);
+	menu.show_all();
+	menu.set_attach_widget(Some(drawing_area));
+	menu.popup_at_pointer(Some(evt));
+}
+
+//Context menu of a log line, actions apply to the clicked entry
+fn show_entry_menu(
+	store_rc: &Rc<RefCell<LogStoreLinear>>,
+	tree_store: &gtk::TreeStore,
+	search_entry: &gtk::SearchEntry,
+	drawing_area: &gtk::DrawingArea,
+	evt: &gdk::EventButton,
+	offset: usize,
+) {
+	let menu = gtk::Menu::new();
+	let store = store_rc.borrow();
+	let entry = &store.store[offset];
+
+	//Adds a menu item whose action gets the store; the tree store and search entry
+	//are borrowed by their own signal handlers, so actions must not hold the store
+	//while calling into them.
+	let add_item = |menu: &gtk::Menu, label: &str, action: Box<dyn Fn()>| {
+		let item = gtk::MenuItem::with_label(label);
+		item.connect_activate(move |_item| action());
+		menu.append(&item);
+		item
+	};
+
+	let message = store.message(offset).to_string();
+	add_item(
+		&menu,
+		"Copy message",
+		Box::new(move || {
+			gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&message);
+		}),
+	);
+	let metadata_text = store.copy_format.format_entry(&store, offset);
+	add_item(
+		&menu,
+		"Copy with metadata",
+		Box::new(move || {
+			gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&metadata_text);
+		}),
+	);
+	let selected = store.selected_offsets();
+	let markdown_text = store.copy_format.format_markdown(
+		&store,
+		if selected.contains(&offset) {
+			&selected
+		} else {
+			std::slice::from_ref(&offset)
+		},
+	);
+	add_item(
+		&menu,
+		"Copy as Markdown table",
+		Box::new(move || {
+			gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&markdown_text);
+		}),
+	);
+	menu.append(&gtk::SeparatorMenuItem::new());
+
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		add_item(
+			&menu,
+			"Set anchor",
+			Box::new(move || {
+				store_rc.borrow_mut().anchor_offset = Some(offset);
+				drawing_area.queue_draw();
+			}),
+		);
+	}
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		let label = if store.bookmarks.contains(&offset) {
+			"Remove bookmark"
+		} else {
+			"Bookmark"
+		};
+		add_item(
+			&menu,
+			label,
+			Box::new(move || {
+				let mut store = store_rc.borrow_mut();
+				if !store.bookmarks.insert(offset) {
+					store.bookmarks.remove(&offset);
+				}
+				drawing_area.queue_draw();
+			}),
+		);
+	}
+	menu.append(&gtk::SeparatorMenuItem::new());
+
+	let source_id = entry.source_id;
+	{
+		let store_rc = store_rc.clone();
+		let tree_store = tree_store.clone();
+		let drawing_area = drawing_area.clone();
+		add_item(
+			&menu,
+			&format!("Show only source {}", store.source_short_name(source_id)),
+			Box::new(move || {
+				if let Some(path) = find_source_row(&tree_store, None, source_id) {
+					let mut store = store_rc.borrow_mut();
+					set_row_active(
+						&tree_store,
+						&mut store,
+						&drawing_area,
+						gtk::TreePath::new_first(),
+						false,
+					);
+					set_row_active(&tree_store, &mut store, &drawing_area, path, true);
+				}
+			}),
+		);
+	}
+	{
+		let store_rc = store_rc.clone();
+		let tree_store = tree_store.clone();
+		let drawing_area = drawing_area.clone();
+		add_item(
+			&menu,
+			&format!("Hide source {}", store.source_short_name(source_id)),
+			Box::new(move || {
+				if let Some(path) = find_source_row(&tree_store, None, source_id) {
+					set_row_active(
+						&tree_store,
+						&mut store_rc.borrow_mut(),
+						&drawing_area,
+						path,
+						false,
+					);
+				}
+			}),
+		);
+	}
+	for (name, value) in entry.custom_fields.iter() {
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		let field_filter = (name.to_string(), value.clone());
+		add_item(
+			&menu,
+			&format!("Show only {} {}", name, value),
+			Box::new(move || {
+				store_rc
+					.borrow_mut()
+					.set_field_filter(Some(field_filter.clone()));
+				drawing_area.queue_draw();
+			}),
+		);
+	}
+	if let Some((name, value)) = &store.field_filter {
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		add_item(
+			&menu,
+			&format!("Clear filter {} {}", name, value),
+			Box::new(move || {
+				store_rc.borrow_mut().set_field_filter(None);
+				drawing_area.queue_draw();
+			}),
+		);
+	}
+	{
+		let search_entry = search_entry.clone();
+		let search_text = store
+			.message(offset)
+			.lines()
+			.next()
+			.unwrap_or("")
+			.to_string();
+		add_item(
+			&menu,
+			"Search for this text",
+			Box::new(move || search_entry.set_text(&search_text)),
+		);
+	}
+	menu.append(&gtk::SeparatorMenuItem::new());
+
+	let surrounding_menu = gtk::Menu::new();
+	for seconds in [1, 5, 30, 300] {
+		let store_rc = store_rc.clone();
+		let tree_store = tree_store.clone();
+		let drawing_area = drawing_area.clone();
+		let timestamp = entry.datetime();
+		let label = if seconds < 60 {
+			format!("±{} s", seconds)
+		} else {
+			format!("±{} min", seconds / 60)
+		};
+		add_item(
+			&surrounding_menu,
+			&label,
+			Box::new(move || {
+				let mut store = store_rc.borrow_mut();
+				//Across all sources: Show the ones that were hidden again
+				set_row_active(
+					&tree_store,
+					&mut store,
+					&drawing_area,
+					gtk::TreePath::new_first(),
+					true,
+				);
+				let window = chrono::Duration::seconds(seconds);
+				store.set_time_range(Some((timestamp - window, timestamp + window)));
+				store.anchor_offset = Some(offset);
+				store.jump_to(offset);
+				drawing_area.queue_draw();
+			}),
+		);
+	}
+	let surrounding_item = gtk::MenuItem::with_label("Show surrounding time");
+	surrounding_item.set_submenu(Some(&surrounding_menu));
+	menu.append(&surrounding_item);
+	if store.time_range.is_some() {
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		add_item(
+			&menu,
+			"Clear time range",
+			Box::new(move || {
+				store_rc.borrow_mut().set_time_range(None);
+				drawing_area.queue_draw();
+			}),
+		);
+	}
+
+	drop(store);
+	menu.show_all();
+	menu.set_attach_widget(Some(drawing_area));
+	menu.popup_at_pointer(Some(evt));
+}
+
+//Path of the tree row showing the log source with the given id
+fn find_source_row(
+	tree_store: &gtk::TreeStore,
+	parent: Option<&gtk::TreeIter>,
+	source_id: u32,
+) -> Option<gtk::TreePath> {
+	let iter = tree_store.iter_children(parent)?;
+	loop {
+		let id = tree_store
+			.value(&iter, LogSourcesColumns::Id as i32)
+			.get::<u32>()
+			.unwrap();
+		if id == source_id {
+			return tree_store.path(&iter);
+		}
+		if let Some(path) = find_source_row(tree_store, Some(&iter), source_id) {
+			return Some(path);
+		}
+		if !tree_store.iter_next(&iter) {
+			return None;
+		}
+	}
+}
+
+//Toggle a row until it is (in)active with all its children, see `toggle_row`
+fn set_row_active(
+	tree_store: &gtk::TreeStore,
+	store: &mut LogStoreLinear,
+	drawing_area: &gtk::DrawingArea,
+	path: gtk::TreePath,
+	active: bool,
+) {
+	//Note: An inconsistent row becomes active first, so two toggles suffice
+	for _ in 0..2 {
+		let iter = tree_store.iter(&path).unwrap();
+		let row_active = tree_store
+			.value(&iter, LogSourcesColumns::Active as i32)
+			.get::<bool>()
+			.unwrap();
+		let inconsistent = tree_store
+			.value(&iter, LogSourcesColumns::Inconsistent as i32)
+			.get::<bool>()
+			.unwrap();
+		if row_active == active && !inconsistent {
+			return;
+		}
+		toggle_row(tree_store, store, drawing_area, path.clone());
+	}
+}
+
+//Lets the user pick a color for a log source and all of its children
+fn choose_source_color(
+	tree_view: &gtk::TreeView,
+	tree_store: &gtk::TreeStore,
+	store_rc: &Rc<RefCell<LogStoreLinear>>,
+	drawing_area: &gtk::DrawingArea,
+	path: &gtk::TreePath,
+) {
+	let iter = tree_store.iter(path).unwrap();
+	let id = tree_store
+		.value(&iter, LogSourcesColumns::Id as i32)
+		.get::<u32>()
+		.unwrap();
+	let color = store_rc.borrow().source_color(id);
+
+	let parent = tree_view
+		.toplevel()
+		.and_then(|toplevel| toplevel.downcast::<gtk::Window>().ok());
+	let dialog = gtk::ColorChooserDialog::new(Some("Log source color"), parent.as_ref());
+	dialog.set_use_alpha(false);
+	dialog.set_rgba(&gdk::RGBA::new(color.0, color.1, color.2, 1.0));
+	let response = dialog.run();
+	let rgba = dialog.rgba();
+	dialog.close();
+	if response != gtk::ResponseType::Ok {
+		return;
+	}
+
+	//Ids of a subtree are contiguous, see `toggle_row`
+	let last_id = {
+		let child_cnt = count_descendants(tree_store, &iter);
+		id + child_cnt
+	};
+	{
+		let mut store = store_rc.borrow_mut();
+		for source_id in id..=last_id {
+			store
+				.source_colors
+				.insert(source_id, (rgba.red(), rgba.green(), rgba.blue()));
+		}
+	}
+	refresh_source_colors(tree_store, &store_rc.borrow());
+	drawing_area.queue_draw();
+}
+
+fn show_preferences_dialog(
+	button: &gtk::Button,
+	settings_rc: &Rc<RefCell<settings::Settings>>,
+	store_rc: &Rc<RefCell<LogStoreLinear>>,
+	drawing_area: &gtk::DrawingArea,
+	default_font: &str,
+) {
+	let parent = button
+		.toplevel()
+		.and_then(|toplevel| toplevel.downcast::<gtk::Window>().ok());
+	let dialog = gtk::Dialog::with_buttons(
+		Some("Preferences"),
+		parent.as_ref(),
+		DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+		&[
+			("Cancel", gtk::ResponseType::Cancel),
+			("Save", gtk::ResponseType::Accept),
+		],
+	);
+	let grid = gtk::Grid::new();
+	grid.set_row_spacing(5);
+	grid.set_column_spacing(10);
+	grid.set_border_width(10);
+	let add_row = |row: i32, label: &str, widget: &gtk::Widget| {
+		let label = gtk::Label::new(Some(label));
+		label.set_halign(gtk::Align::Start);
+		grid.attach(&label, 0, row, 1, 1);
+		grid.attach(widget, 1, row, 1, 1);
+	};
+
+	let settings = settings_rc.borrow();
+	let font_btn = gtk::FontButton::with_font(&store_rc.borrow().font.to_string());
+	font_btn.set_use_font(true);
+	add_row(0, "Font", font_btn.upcast_ref());
+
+	let padding_spin = gtk::SpinButton::with_range(0.0, 20.0, 1.0);
+	padding_spin.set_value(settings.row_padding);
+	add_row(1, "Row padding", padding_spin.upcast_ref());
+
+	let scroll_spin = gtk::SpinButton::with_range(1.0, 20.0, 1.0);
+	scroll_spin.set_value(settings.scroll_speed as f64);
+	add_row(2, "Scroll speed (rows)", scroll_spin.upcast_ref());
+
+	let format_entry = gtk::Entry::new();
+	format_entry.set_text(&settings.timestamp_format);
+	format_entry.set_tooltip_text(Some("chrono format, e.g. %d.%m.%y %T or %Y-%m-%d %T"));
+	add_row(3, "Timestamp format", format_entry.upcast_ref());
+
+	let precision_combo = gtk::ComboBoxText::new();
+	for precision in settings::TIMESTAMP_PRECISIONS {
+		precision_combo.append(Some(&precision.to_string()), &precision.to_string());
+	}
+	precision_combo.set_active_id(Some(&settings.timestamp_precision.to_string()));
+	add_row(4, "Fractional digits", precision_combo.upcast_ref());
+
+	let time_zone_combo = gtk::ComboBoxText::with_entry();
+	time_zone_combo.append_text("UTC");
+	time_zone_combo.append_text("Local");
+	if let Some(entry) = time_zone_combo
+		.child()
+		.and_then(|child| child.downcast::<gtk::Entry>().ok())
+	{
+		entry.set_text(&settings.time_zone.to_string());
+		entry.set_tooltip_text(Some("UTC, Local or an offset like +02:00"));
+	}
+	add_row(5, "Time zone", time_zone_combo.upcast_ref());
+
+	let theme_box = gtk::Box::new(Orientation::Horizontal, 5);
+	let theme_combo = gtk::ComboBoxText::new();
+	for kind in theme::ThemeKind::ALL {
+		theme_combo.append(Some(kind.id()), kind.label());
+	}
+	theme_combo.set_active_id(Some(settings.theme.id()));
+	theme_box.pack_start(&theme_combo, true, true, 0);
+	let colors_btn = gtk::Button::with_label("Severity colors…");
+	{
+		let store_rc = store_rc.clone();
+		let drawing_area = drawing_area.clone();
+		colors_btn.connect_clicked(move |w| {
+			show_severity_colors_dialog(w, &store_rc, &drawing_area);
+		});
+	}
+	theme_box.pack_start(&colors_btn, false, false, 0);
+	add_row(6, "Theme", theme_box.upcast_ref());
+
+	let severity_box = gtk::Box::new(Orientation::Horizontal, 5);
+	let severity_checks: Vec<gtk::CheckButton> = ["Crit", "Err", "Warn", "Info", "Dbg", "Trace"]
+		.iter()
+		.zip(settings.severity_visibility())
+		.map(|(label, show)| {
+			let check_btn = gtk::CheckButton::with_label(label);
+			check_btn.set_active(show);
+			severity_box.pack_start(&check_btn, false, false, 0);
+			check_btn
+		})
+		.collect();
+	add_row(7, "Shown severities", severity_box.upcast_ref());
+
+	let log_level_combo = gtk::ComboBoxText::new();
+	for level in settings::LOG_LEVELS {
+		log_level_combo.append(Some(level.as_str()), level.as_str());
+	}
+	log_level_combo.set_active_id(Some(settings.log_level.as_str()));
+	add_row(8, "Log output", log_level_combo.upcast_ref());
+
+	let copy_template_entry = gtk::Entry::new();
+	copy_template_entry.set_text(&settings.copy_template);
+	copy_template_entry.set_tooltip_text(Some(
+		"Placeholders: {timestamp} {severity} {source} {path} {message} {field:<name>}",
+	));
+	add_row(9, "Copy format", copy_template_entry.upcast_ref());
+
+	let copy_format_entry = gtk::Entry::new();
+	copy_format_entry.set_text(&settings.copy_timestamp_format);
+	copy_format_entry.set_tooltip_text(Some("chrono format, e.g. %d-%m-%y %T%.6f"));
+	add_row(10, "Copied timestamps", copy_format_entry.upcast_ref());
+
+	let line_ending_combo = gtk::ComboBoxText::new();
+	for line_ending in copy_format::LineEnding::ALL {
+		line_ending_combo.append(Some(line_ending.id()), line_ending.label());
+	}
+	line_ending_combo.set_active_id(Some(settings.copy_line_ending.id()));
+	add_row(11, "Copied line endings", line_ending_combo.upcast_ref());
+	//Not editable in the dialog
+	let correction_rules = settings.correction_rules.clone();
+	drop(settings);
+
+	dialog.content_area().add(&grid);
+	dialog.show_all();
+
+	let new_settings = loop {
+		if dialog.run() != gtk::ResponseType::Accept {
+			break None;
+		}
+		let timestamp_format = format_entry.text().to_string();
+		let copy_timestamp_format = copy_format_entry.text().to_string();
+		let time_zone_text = time_zone_combo
+			.active_text()
+			.map(|text| text.to_string())
+			.unwrap_or_default();
+		let time_zone = settings::TimeZone::parse(&time_zone_text);
+		let error = if !settings::is_valid_timestamp_format(&timestamp_format) {
+			Some(format!("Invalid timestamp format: {}", timestamp_format))
+		} else if !settings::is_valid_timestamp_format(&copy_timestamp_format) {
+			Some(format!(
+				"Invalid timestamp format: {}",
+				copy_timestamp_format
+			))
+		} else if time_zone.is_none() {
+			Some(format!("Invalid time zone: {}", time_zone_text))
+		} else {
+			None
+		};
+		if let Some(error) = error {
+			let error_dialog = MessageDialog::new(
+				Some(&dialog),
+				DialogFlags::MODAL,
+				MessageType::Error,
+				ButtonsType::Ok,
+				&error,
+			);
+			error_dialog.run();
+			error_dialog.close();
+			continue;
+		}
+
+		let visibility: Vec<bool> = severity_checks.iter().map(|c| c.is_active()).collect();
+		break Some(settings::Settings {
+			font: font_btn.font().map(|font| font.to_string()),
+			row_padding: padding_spin.value(),
+			scroll_speed: scroll_spin.value() as i64,
+			timestamp_format,
+			timestamp_precision: precision_combo
+				.active_id()
+				.and_then(|id| id.parse().ok())
+				.unwrap_or(3),
+			time_zone: time_zone.unwrap(),
+
+			show_crit: visibility[0],
+			show_err: visibility[1],
+			show_warn: visibility[2],
+			show_info: visibility[3],
+			show_dbg: visibility[4],
+			show_trace: visibility[5],
+
+			theme: theme_combo
+				.active_id()
+				.and_then(|id| theme::ThemeKind::from_id(&id))
+				.unwrap_or(theme::ThemeKind::System),
+			severity_colors: store_rc.borrow().severity_overrides,
+			log_level: log_level_combo
+				.active_id()
+				.and_then(|id| id.parse().ok())
+				.unwrap_or(log::LevelFilter::Trace),
+
+			copy_template: copy_template_entry.text().to_string(),
+			copy_timestamp_format,
+			copy_line_ending: line_ending_combo
+				.active_id()
+				.and_then(|id| copy_format::LineEnding::from_id(&id))
+				.unwrap_or(copy_format::LineEnding::Native),
+
+			correction_rules: correction_rules.clone(),
+		});
+	};
+	dialog.close();
+
+	if let Some(new_settings) = new_settings {
+		if let Err(err) = new_settings.save() {
+			log::warn!(
+				"Could not save settings {:?}: {}",
+				settings::Settings::path(),
+				err
+			);
+		}
+		log::set_max_level(new_settings.log_level);
+		*settings_rc.borrow_mut() = new_settings;
+	}
+	//Also reverts severity colors that were changed before cancelling
+	settings_rc
+		.borrow()
+		.apply(&mut store_rc.borrow_mut(), default_font);
+	drawing_area.queue_draw();
+}
+
+fn system_prefers_dark() -> bool {
+	//Note: Properties are read by name, as the name of gtk's `SettingsExt` clashes with gio's
+	gtk::Settings::default().is_some_and(|settings| {
+		settings.property::<bool>("gtk-application-prefer-dark-theme")
+			|| settings
+				.property::<Option<String>>("gtk-theme-name")
+				.is_some_and(|name| name.to_lowercase().contains("dark"))
+	})
+}
+
+//Per-severity foreground and optional background colors, overriding the theme
+fn show_severity_colors_dialog(
+	button: &gtk::Button,
+	store_rc: &Rc<RefCell<LogStoreLinear>>,
+	drawing_area: &gtk::DrawingArea,
+) {
+	let parent = button
+		.toplevel()
+		.and_then(|toplevel| toplevel.downcast::<gtk::Window>().ok());
+	let dialog = gtk::Dialog::with_buttons(
+		Some("Severity colors"),
+		parent.as_ref(),
+		DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+		&[
+			("Reset", gtk::ResponseType::Reject),
+			("Close", gtk::ResponseType::Close),
+		],
+	);
+	let grid = gtk::Grid::new();
+	grid.set_row_spacing(5);
+	grid.set_column_spacing(10);
+	grid.set_border_width(10);
+	grid.attach(&gtk::Label::new(Some("Foreground")), 1, 0, 1, 1);
+	grid.attach(&gtk::Label::new(Some("Background")), 2, 0, 2, 1);
+
+	let to_rgba = |color: theme::Color| gdk::RGBA::new(color.0, color.1, color.2, 1.0);
+	let from_rgba = |rgba: gdk::RGBA| (rgba.red(), rgba.green(), rgba.blue());
+	let severities = [
+		model::LogLevel::Critical,
+		model::LogLevel::Error,
+		model::LogLevel::Warning,
+		model::LogLevel::Info,
+		model::LogLevel::Debug,
+		model::LogLevel::Trace,
+	];
+	for (row, severity) in severities.iter().enumerate() {
+		let idx = theme::severity_index(severity);
+		let colors = *store_rc.borrow().theme.severity(severity);
+		let background = colors
+			.background
+			.unwrap_or(store_rc.borrow().theme.background);
+
+		let fg_btn = gtk::ColorButton::with_rgba(&to_rgba(colors.foreground));
+		let bg_check = gtk::CheckButton::new();
+		bg_check.set_active(colors.background.is_some());
+		let bg_btn = gtk::ColorButton::with_rgba(&to_rgba(background));
+		bg_btn.set_sensitive(colors.background.is_some());
+
+		//Any change stores all colors of the severity as user override
+		let apply = {
+			let store_rc = store_rc.clone();
+			let drawing_area = drawing_area.clone();
+			let fg_btn = fg_btn.clone();
+			let bg_check = bg_check.clone();
+			let bg_btn = bg_btn.clone();
+			move || {
+				bg_btn.set_sensitive(bg_check.is_active());
+				let mut store = store_rc.borrow_mut();
+				store.severity_overrides[idx] = Some(theme::SeverityColors {
+					foreground: from_rgba(fg_btn.rgba()),
+					background: if bg_check.is_active() {
+						Some(from_rgba(bg_btn.rgba()))
+					} else {
+						None
+					},
+				});
+				store.update_theme();
+				drawing_area.queue_draw();
+			}
+		};
+		let apply_clone = apply.clone();
+		fg_btn.connect_color_set(move |_| apply_clone());
+		let apply_clone = apply.clone();
+		bg_check.connect_toggled(move |_| apply_clone());
+		bg_btn.connect_color_set(move |_| apply());
+
+		let label = gtk::Label::new(Some(&severity.to_string()));
+		label.set_halign(gtk::Align::Start);
+		grid.attach(&label, 0, row as i32 + 1, 1, 1);
+		grid.attach(&fg_btn, 1, row as i32 + 1, 1, 1);
+		grid.attach(&bg_check, 2, row as i32 + 1, 1, 1);
+		grid.attach(&bg_btn, 3, row as i32 + 1, 1, 1);
+	}
+	dialog.content_area().add(&grid);
+	dialog.show_all();
+	let response = dialog.run();
+	dialog.close();
+
+	if response == gtk::ResponseType::Reject {
+		let mut store = store_rc.borrow_mut();
+		store.severity_overrides = [None; 6];
+		store.update_theme();
+		drawing_area.queue_draw();
+	}
+}
+
+fn count_descendants(tree_store: &gtk::TreeStore, iter: &gtk::TreeIter) -> u32 {
+	let mut count = 0;
+	if let Some(child) = tree_store.iter_children(Some(iter)) {
+		loop {
+			count += 1 + count_descendants(tree_store, &child);
+			if !tree_store.iter_next(&child) {
+				break;
+			}
+		}
+	}
+	count
+}
+
+//Updates the color column of the log source tree after colors were changed
+fn refresh_source_colors(tree_store: &gtk::TreeStore, store: &LogStoreLinear) {
+	fn refresh(
+		tree_store: &gtk::TreeStore,
+		parent: Option<&gtk::TreeIter>,
+		store: &LogStoreLinear,
+	) {
+		if let Some(iter) = tree_store.iter_children(parent) {
+			loop {
+				if tree_store.iter_has_child(&iter) {
+					refresh(tree_store, Some(&iter), store);
+				} else {
+					let id = tree_store
+						.value(&iter, LogSourcesColumns::Id as i32)
+						.get::<u32>()
+						.unwrap();
+					tree_store.set_value(
+						&iter,
+						LogSourcesColumns::Color as u32,
+						&log_store::color_to_hex(store.source_color(id)).to_value(),
+					);
+				}
+				if !tree_store.iter_next(&iter) {
+					break;
+				}
+			}
+		}
+	}
+	refresh(tree_store, None, store);
+}
+
 fn gio_files_to_paths(gio_files: &[gio::File]) -> Vec<std::path::PathBuf> {
 	let mut result = Vec::new();
 	for gio_file in gio_files {
@@ -1478,6 +3793,11 @@ fn main() {
 		// Apply globally
 		.apply()
 		.unwrap();
+	//Cap the verbosity configured by the user, see `show_preferences_dialog`
+	match settings::Settings::load() {
+		Ok(settings) => log::set_max_level(settings.log_level),
+		Err(err) => log::warn!("Could not load settings: {}", err),
+	}
 
 	// https://developer.gnome.org/CommandLine/
 	// https://developer.gnome.org/GtkApplication/
@@ -1529,13 +3849,40 @@ fn main() {
 		Some("This is a test argument"),
 	);
 
+	application.add_main_option(
+		"password",
+		glib::Char::from(b'p'),
+		glib::OptionFlags::IN_MAIN,
+		glib::OptionArg::StringArray,
+		"Password of encrypted sfiles, tried before the ones in the settings (repeatable)",
+		Some("PASSWORD"),
+	);
+
+	//Parsed by the launching process, only used if it becomes the primary instance
+	let cli_passwords: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
+	{
+		let cli_passwords = cli_passwords.clone();
+		application.connect_handle_local_options(move |_app, options| {
+			if let Some(passwords) = options
+				.lookup_value("password", None)
+				.and_then(|value| value.get::<Vec<String>>())
+			{
+				*cli_passwords.borrow_mut() = passwords;
+			}
+			-1 //continue with the default handling
+		});
+	}
+
 	// https://gtk-rs.org/docs/gio/prelude/trait.ApplicationExtManual.html
-	application.connect_open(move |app, gio_files, _| {
-		build_ui(app, &gio_files_to_paths(gio_files));
-	});
+	{
+		let cli_passwords = cli_passwords.clone();
+		application.connect_open(move |app, gio_files, _| {
+			build_ui(app, &gio_files_to_paths(gio_files), &cli_passwords.borrow());
+		});
+	}
 
-	application.connect_activate(|app| {
-		build_ui(app, &Vec::new());
+	application.connect_activate(move |app| {
+		build_ui(app, &Vec::new(), &cli_passwords.borrow());
 	});
 
 	// https://gtk-rs.org/docs/gio/prelude/trait.ApplicationExtManual.html#tymethod.run
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Message text of all log entries in one buffer. Entries refer to their
+// message by id. Logs repeat the same lines over and over, so identical
+// messages are stored only once (interned) while the arena is built.
+//
+// Messages of huge plain-text logs are not copied at all: They stay in the
+// file contents and are only referred to by their position, see
+// `MessageArenaBuilder::map`. Their ids have the `MAPPED` bit set.
+
+const MAPPED: u32 = 1 << 31;
+
+//Shown instead of mapped messages once the file shrank, see `MessageArena::check_file`
+pub const TRUNCATED_MESSAGE: &str = "<log file was truncated while open>";
+//Shown instead of a mapped message that is no valid UTF-8 anymore, see `MessageArena::get`
+pub const CHANGED_MESSAGE: &str = "<log file was changed while open>";
+
+//Contents of a file whose messages are referred to by position, see `rds_log::map_file`
+pub enum FileContents {
+	Memory(Box<[u8]>),
+	//The file is kept open to notice truncation. Truncating it while mapped may still crash the app
+	//between two checks, rewriting it in place shows the new contents; appending and rotating by
+	//renaming are fine.
+	Mapped(memmap2::Mmap, std::fs::File),
+}
+
+impl std::ops::Deref for FileContents {
+	type Target = [u8];
+	fn deref(&self) -> &[u8] {
+		match self {
+			FileContents::Memory(data) => data,
+			FileContents::Mapped(mmap, _) => mmap,
+		}
+	}
+}
+
+#[derive(Default)]
+pub struct MessageArena {
+	text: String,
+	ends: Vec<usize>, //end of each message in `text`, indexed by message id
+	file: Option<FileContents>,
+	spans: Vec<(usize, usize)>, //start and end of each mapped message in `file`, see `map_span`
+	truncated: AtomicBool,      //mapped file shrank, its messages must not be accessed anymore
+}
+
+impl MessageArena {
+	pub fn get(&self, id: u32) -> &str {
+		if id & MAPPED != 0 {
+			if self.truncated.load(Ordering::Relaxed) {
+				return TRUNCATED_MESSAGE;
+			}
+			let (start, end) = self.spans[(id & !MAPPED) as usize];
+			//Checked again, as the file may have been rewritten since `map_span` validated it
+			return match &self.file {
+				Some(file) => std::str::from_utf8(&file[start..end]).unwrap_or(CHANGED_MESSAGE),
+				None => "",
+			};
+		}
+		let id = id as usize;
+		let start = if id == 0 { 0 } else { self.ends[id - 1] };
+		&self.text[start..self.ends[id]]
+	}
+
+	//Whether the mapped messages can still be accessed. Accessing a memory-mapped file beyond
+	//its end crashes the app, so this is checked before drawing and searching; once the file
+	//shrank, `get` only returns `TRUNCATED_MESSAGE` for them.
+	pub fn check_file(&self) -> bool {
+		if self.truncated.load(Ordering::Relaxed) {
+			return false;
+		}
+		if let Some(FileContents::Mapped(mmap, file)) = &self.file {
+			let len = file.metadata().map_or(0, |metadata| metadata.len());
+			if len < mmap.len() as u64 {
+				log::error!(
+					"Log file shrank from {} to {} bytes while open, its messages are no longer shown",
+					mmap.len(),
+					len
+				);
+				self.truncated.store(true, Ordering::Relaxed);
+				return false;
+			}
+		}
+		true
+	}
+
+	//Number of distinct messages
+	pub fn len(&self) -> usize {
+		self.ends.len() + self.spans.len()
+	}
+
+	//Mapped messages are not counted, they are paged in and out by the OS
+	pub fn heap_size(&self) -> usize {
+		self.text.capacity()
+			+ self.ends.capacity() * std::mem::size_of::<usize>()
+			+ self.spans.capacity() * std::mem::size_of::<(usize, usize)>()
+	}
+}
+
+#[derive(Default)]
+pub struct MessageArenaBuilder {
+	arena: MessageArena,
+	ids: HashMap<u64, u32>, //hash of a message to the id of its first occurrence
+}
+
+impl MessageArenaBuilder {
+	//Id of `message`, which is only added if it is not in the arena yet
+	pub fn intern(&mut self, message: &str) -> u32 {
+		let mut hasher = std::collections::hash_map::DefaultHasher::new();
+		message.hash(&mut hasher);
+		let hash = hasher.finish();
+		if let Some(id) = self.ids.get(&hash) {
+			if self.arena.get(*id) == message {
+				return *id;
+			}
+			//Hash collision, the message is simply stored twice
+		}
+		let id = self.arena.ends.len() as u32;
+		self.arena.text += message;
+		self.arena.ends.push(self.arena.text.len());
+		self.ids.entry(hash).or_insert(id);
+		id
+	}
+
+	//Keeps `file` so that `map_span` can refer to messages in it. Only one file per arena.
+	pub fn map(&mut self, file: FileContents) {
+		self.arena.file = Some(file);
+	}
+
+	//Id of the message at `start..end` of the mapped file. Messages that cannot be shown as they
+	//are (invalid UTF-8, NUL bytes which GTK has trouble with, CRLF line breaks of multi-line
+	//messages) are cleaned up and interned.
+	pub fn map_span(&mut self, start: usize, end: usize) -> u32 {
+		let bytes = match &self.arena.file {
+			Some(file) => &file[start..end],
+			None => &[],
+		};
+		match std::str::from_utf8(bytes) {
+			Ok(message) if !message.contains('\0') && !message.contains("\r\n") => {
+				self.arena.spans.push((start, end));
+				(self.arena.spans.len() - 1) as u32 | MAPPED
+			}
+			_ => {
+				let message = String::from_utf8_lossy(bytes)
+					.replace('\0', "")
+					.replace("\r\n", "\n");
+				self.intern(&message)
+			}
+		}
+	}
+
+	pub fn finish(mut self) -> MessageArena {
+		self.arena.text.shrink_to_fit();
+		self.arena.ends.shrink_to_fit();
+		self.arena.spans.shrink_to_fit();
+		self.arena
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_intern() {
+		let mut builder = MessageArenaBuilder::default();
+		let ids: Vec<u32> = ["first", "", "second", "first", "", "äöü"]
+			.iter()
+			.map(|message| builder.intern(message))
+			.collect();
+		assert_eq!(ids, vec![0, 1, 2, 0, 1, 3]);
+		let arena = builder.finish();
+		assert_eq!(arena.len(), 4);
+		assert_eq!(arena.get(0), "first");
+		assert_eq!(arena.get(1), "");
+		assert_eq!(arena.get(2), "second");
+		assert_eq!(arena.get(3), "äöü");
+	}
+
+	#[test]
+	fn test_map_span() {
+		let mut builder = MessageArenaBuilder::default();
+		builder.map(FileContents::Memory(Box::from(
+			&b"valid|inv\xffalid|nul\0|crlf\r\nline\r\nline"[..],
+		)));
+		let ids = [
+			builder.map_span(0, 5),
+			builder.map_span(6, 14),
+			builder.map_span(15, 19),
+			builder.map_span(20, 36),
+		];
+		assert_eq!(ids[0], MAPPED);
+		assert_eq!(ids[1] & MAPPED, 0);
+		let mut arena = builder.finish();
+		assert!(arena.check_file());
+		assert_eq!(arena.get(ids[0]), "valid");
+		assert_eq!(arena.get(ids[1]), "inv\u{fffd}alid");
+		assert_eq!(arena.get(ids[2]), "nul");
+		assert_eq!(arena.get(ids[3]), "crlf\nline\nline");
+		//Rewritten in place after `map_span`
+		arena.file = Some(FileContents::Memory(Box::from(&b"in\xff\xffd"[..])));
+		assert_eq!(arena.get(ids[0]), CHANGED_MESSAGE);
+	}
+}
@@ -33,6 +33,7 @@ impl fmt::Display for LogLevel {
 }
 
 #[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum CustomField {
 	Int64(i64),
 	Int32(i32),
@@ -47,6 +48,29 @@ pub enum CustomField {
 	String(String),
 }
 
+impl fmt::Display for CustomField {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			CustomField::Int64(v) => write!(f, "{}", v),
+			CustomField::Int32(v) => write!(f, "{}", v),
+			CustomField::Int16(v) => write!(f, "{}", v),
+			CustomField::Int8(v) => write!(f, "{}", v),
+			CustomField::UInt64(v) => write!(f, "{}", v),
+			CustomField::UInt32(v) => write!(f, "{}", v),
+			CustomField::UInt16(v) => write!(f, "{}", v),
+			CustomField::UInt8(v) => write!(f, "{}", v),
+			CustomField::Float32(v) => write!(f, "{}", v),
+			CustomField::Float64(v) => write!(f, "{}", v),
+			CustomField::String(v) => write!(f, "{}", v),
+		}
+	}
+}
+
+// Timestamps before 01-01-72 00:00:00.000000 are not realistic because the devices did not exist back then.
+// They are relative to the boot of the device (uptime) and were never corrected with EtherCAT time.
+// It is reasonable to assume that a device receives its EtherCAT time within 2 years (or never).
+pub const BOOT_RELATIVE_LIMIT_SECS: i64 = 63_072_000;
+
 pub struct LogEntry {
 	pub timestamp: chrono::DateTime<Utc>,
 	pub severity: LogLevel,
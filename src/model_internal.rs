@@ -2,31 +2,94 @@ extern crate chrono;
 
 use chrono::prelude::*;
 
+use crate::message_arena;
 use crate::model;
 
 pub const VISIBLE_ON: u8 = 0x0;
 pub const VISIBLE_OFF_SOURCE: u8 = 0x1;
 pub const VISIBLE_OFF_SEVERITY: u8 = 0x2;
 pub const VISIBLE_OFF_FILTER: u8 = 0x4;
+pub const VISIBLE_OFF_FIELD: u8 = 0x8;
+pub const VISIBLE_OFF_TIME: u8 = 0x10;
+
+// Nanoseconds since 1970-01-01 00:00:00 UTC. Smaller and faster to compare than
+// `DateTime<Utc>`, covers the years 1677 to 2262 (timestamps outside are clamped).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+	pub fn from_datetime(datetime: &DateTime<Utc>) -> Timestamp {
+		const MAX_SECS: i64 = i64::MAX / 1_000_000_000 - 1;
+		let secs = datetime.timestamp();
+		if secs > MAX_SECS {
+			Timestamp(i64::MAX)
+		} else if secs < -MAX_SECS {
+			Timestamp(i64::MIN)
+		} else {
+			Timestamp(secs * 1_000_000_000 + datetime.timestamp_subsec_nanos() as i64)
+		}
+	}
+
+	pub fn to_datetime(self) -> DateTime<Utc> {
+		Utc.timestamp_nanos(self.0)
+	}
+
+	pub fn offset(self, nanos: i64) -> Timestamp {
+		Timestamp(self.0.saturating_add(nanos))
+	}
+
+	//Uptime of the device rather than a date, see `model::BOOT_RELATIVE_LIMIT_SECS`.
+	//Negative timestamps are real (or clamped) dates before 1970, not uptimes.
+	pub fn is_boot_relative(self) -> bool {
+		const LIMIT_NS: i64 = model::BOOT_RELATIVE_LIMIT_SECS * 1_000_000_000;
+		(0..LIMIT_NS).contains(&self.0)
+	}
+
+	//Time since boot of a boot-relative timestamp
+	pub fn uptime(self) -> chrono::Duration {
+		chrono::Duration::nanoseconds(self.0)
+	}
+}
+
+impl std::ops::Sub for Timestamp {
+	type Output = chrono::Duration;
+
+	fn sub(self, other: Timestamp) -> chrono::Duration {
+		chrono::Duration::nanoseconds(self.0.saturating_sub(other.0))
+	}
+}
 
 #[rustfmt::skip]
 pub struct LogEntryExt {
-	pub timestamp: chrono::DateTime<Utc>,
+	pub timestamp: Timestamp,
 	pub severity: model::LogLevel,
-	pub message: String,
+	pub message: u32, //id in the message arena of the store, see `LogStoreLinear::message`
 	pub source_id: u32,
+	pub custom_fields: Box<[(std::borrow::Cow<'static, str>, model::CustomField)]>, //sorted by name
 	pub visible: u8,
 	pub entry_id: u32, //id (NOT offset) of entry, only active elements have id
 	pub prev_offset: u32, //offset of next active element in LogStoreLinear vec
 	pub next_offset: u32, //offset of previous active element in LogStoreLinear vec
 	//For 1 million objects, 3 uint32 require 20ms more to sort.
 	//Therefore, stick to uint32 and not usize which doubles this amount
+	//48 bytes per entry (was 72 bytes plus an allocation per message with `DateTime` and `String`)
 }
 
 impl LogEntryExt {
 	pub fn is_visible(&self) -> bool {
 		self.visible == VISIBLE_ON
 	}
+
+	pub fn datetime(&self) -> DateTime<Utc> {
+		self.timestamp.to_datetime()
+	}
+
+	pub fn custom_field(&self, name: &str) -> Option<&model::CustomField> {
+		self.custom_fields
+			.binary_search_by(|(field_name, _)| field_name.as_ref().cmp(name))
+			.ok()
+			.map(|idx| &self.custom_fields[idx].1)
+	}
 }
 
 // Extended log source (not part of the API)
@@ -53,23 +116,33 @@ fn remove_nul_bytes(string: String) -> String {
 }
 
 impl LogSourceExt {
-	pub fn from_source(log_source: model::LogSource) -> LogSourceExt {
+	//Messages are moved into `messages`, see `LogStoreLinear::message`
+	pub fn from_source(
+		log_source: model::LogSource,
+		messages: &mut message_arena::MessageArenaBuilder,
+	) -> LogSourceExt {
 		let children = match log_source.children {
 			model::LogSourceContents::Sources(v) => {
 				let mut contents = Vec::<LogSourceExt>::new();
 				contents.reserve(v.len());
 				for source in v {
-					contents.push(LogSourceExt::from_source(source));
+					contents.push(LogSourceExt::from_source(source, messages));
 				}
 				LogSourceContentsExt::Sources(contents)
 			}
 			model::LogSourceContents::Entries(v) => LogSourceContentsExt::Entries(
 				v.into_iter()
-					.map(move |entry| LogEntryExt {
-						timestamp: entry.timestamp,
+					.map(|entry| LogEntryExt {
+						timestamp: Timestamp::from_datetime(&entry.timestamp),
 						severity: entry.severity,
-						message: remove_nul_bytes(entry.message),
+						message: messages.intern(&remove_nul_bytes(entry.message)),
 						source_id: 0,
+						custom_fields: {
+							let mut custom_fields =
+								entry.custom_fields.into_iter().collect::<Vec<_>>();
+							custom_fields.sort_by(|a, b| a.0.cmp(&b.0));
+							custom_fields.into_boxed_slice()
+						},
 						visible: VISIBLE_ON,
 						entry_id: 0,
 						prev_offset: 0,
@@ -89,6 +162,19 @@ impl LogSourceExt {
 		source_ext
 	}
 
+	//For parsers that create the extended entries directly, see `rds_log::map_file`
+	pub fn from_entries(name: String, entries: Vec<LogEntryExt>) -> LogSourceExt {
+		let mut source_ext = LogSourceExt {
+			name: remove_nul_bytes(name),
+			id: 0,
+			child_cnt: 0,
+			children: LogSourceContentsExt::Entries(entries),
+		};
+		source_ext.generate_ids();
+		source_ext.calc_child_cnt();
+		source_ext
+	}
+
 	fn calc_child_cnt(&mut self) {
 		self.child_cnt = match &mut self.children {
 			LogSourceContentsExt::Sources(v) => {
@@ -122,3 +208,93 @@ impl LogSourceExt {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_timestamp() {
+		let datetime = Utc.with_ymd_and_hms(2023, 7, 1, 12, 30, 0).unwrap()
+			+ chrono::Duration::nanoseconds(123_456_789);
+		let timestamp = Timestamp::from_datetime(&datetime);
+		assert_eq!(timestamp.to_datetime(), datetime);
+		let before = Timestamp::from_datetime(&(datetime - chrono::Duration::microseconds(1)));
+		assert!(before < timestamp);
+		assert_eq!(timestamp - before, chrono::Duration::microseconds(1));
+		//Clamped
+		let far = Utc.with_ymd_and_hms(3000, 1, 1, 0, 0, 0).unwrap();
+		assert_eq!(Timestamp::from_datetime(&far), Timestamp(i64::MAX));
+		assert_eq!(std::mem::size_of::<LogEntryExt>(), 48);
+	}
+
+	#[test]
+	fn test_boot_relative() {
+		assert!(Timestamp(0).is_boot_relative());
+		let uptime = Utc.timestamp_opt(3600, 0).unwrap();
+		assert!(Timestamp::from_datetime(&uptime).is_boot_relative());
+		let real = Utc.with_ymd_and_hms(2023, 7, 1, 12, 30, 0).unwrap();
+		assert!(!Timestamp::from_datetime(&real).is_boot_relative());
+		let before_1970 = Utc.with_ymd_and_hms(1969, 12, 31, 23, 0, 0).unwrap();
+		assert!(!Timestamp::from_datetime(&before_1970).is_boot_relative());
+		let far = Utc.with_ymd_and_hms(1000, 1, 1, 0, 0, 0).unwrap();
+		assert!(!Timestamp::from_datetime(&far).is_boot_relative()); //clamped
+	}
+
+	//Run with `cargo test --release measure_store_memory -- --ignored --nocapture`
+	//Result: 458 MiB entries + 43 MiB messages, before 687 MiB + 332 MiB (+ allocator overhead)
+	#[test]
+	#[ignore]
+	fn measure_store_memory() {
+		const ENTRIES: usize = 10_000_000;
+		const REPEATED: [&str; 4] = [
+			"Heartbeat received from controller",
+			"Setting EtherCAT time [delta = 12 ms]",
+			"Axis 2: position reached",
+			"Watchdog: cycle time exceeded, continuing",
+		];
+		let mut messages = message_arena::MessageArenaBuilder::default();
+		let mut message_bytes = 0;
+		let start = Utc.with_ymd_and_hms(2023, 7, 1, 0, 0, 0).unwrap();
+		let store: Vec<LogEntryExt> = (0..ENTRIES)
+			.map(|idx| {
+				//Every tenth message is unique
+				let message = if idx % 10 == 0 {
+					format!("Request {} finished after {} ms", idx, idx % 977)
+				} else {
+					REPEATED[idx % REPEATED.len()].to_string()
+				};
+				message_bytes += message.len();
+				LogEntryExt {
+					timestamp: Timestamp::from_datetime(
+						&(start + chrono::Duration::microseconds(idx as i64 * 250)),
+					),
+					severity: model::LogLevel::Info,
+					message: messages.intern(&message),
+					source_id: (idx % 8) as u32,
+					custom_fields: Box::new([]),
+					visible: VISIBLE_ON,
+					entry_id: 0,
+					prev_offset: 0,
+					next_offset: 0,
+				}
+			})
+			.collect();
+		let messages = messages.finish();
+		let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+		let entries = store.capacity() * std::mem::size_of::<LogEntryExt>();
+		println!(
+			"{} entries: {:.0} MiB entries + {:.0} MiB messages ({} distinct)",
+			ENTRIES,
+			mib(entries),
+			mib(messages.heap_size()),
+			messages.len()
+		);
+		//`DateTime<Utc>` and an owned `String` per entry, not counting allocator overhead
+		println!(
+			"Before: {:.0} MiB entries + {:.0} MiB messages",
+			mib(ENTRIES * 72),
+			mib(message_bytes)
+		);
+	}
+}
@@ -9,6 +9,7 @@ use std::convert::TryFrom;
 // 100-nanosecond offset from 0000-01-01 00:00:00.000 to 1970-01-01 00:00:00.000
 const ZERO_OFFSET_100NS: u64 = 621_355_968_000_000_000;
 // 1-second offset from 0000-01-01 00:00:00.000 to 1970-01-01 00:00:00.000
+#[allow(dead_code)]
 const ZERO_OFFSET_1SEC: i64 = 62_135_596_800;
 
 pub fn from_100ns(gcom_datetime: u64) -> Option<chrono::DateTime<Utc>> {
@@ -37,6 +38,8 @@ pub fn from_100ns(gcom_datetime: u64) -> Option<chrono::DateTime<Utc>> {
 	}
 }
 
+//Inverse of `from_100ns`
+#[allow(dead_code)]
 pub fn to_100ns(datetime: chrono::DateTime<Utc>) -> Option<u64> {
 	const SECONDS_FACTOR: u64 = 10_000_000;
 	const NANOSECONDS_FACTOR: u32 = 100;
@@ -64,6 +67,8 @@ pub fn to_100ns(datetime: chrono::DateTime<Utc>) -> Option<u64> {
 	}
 }
 
+//Timestamp corrections do this on `Timestamp` now, see `correction`
+#[allow(dead_code)]
 pub fn add_offset_100ns(
 	datetime: chrono::DateTime<Utc>,
 	gcom_timespan: i64,
@@ -165,4 +170,17 @@ mod tests {
 			"2020-06-26T15:38:19.085750200Z"
 		)
 	}
+
+	#[test]
+	fn test_timestamp_offset() {
+		// Timestamp corrections shift `Timestamp` instead, see `correction`
+		use crate::model_internal::Timestamp;
+		let dt1 = from_100ns(637287826990872490).expect("Conversion to Rust DateTime failed");
+		for gcom_timespan in [-14_988, 0, 15_620_600_321_009_541] {
+			let dt2 = Timestamp::from_datetime(&dt1)
+				.offset(gcom_timespan * 100)
+				.to_datetime();
+			assert_eq!(Some(dt2), add_offset_100ns(dt1, gcom_timespan));
+		}
+	}
 }
@@ -202,8 +202,7 @@ impl GlogParser {
 							//TODO: Expose ErrorCode to user
 						}
 						GlogSectionKind::SessionId => {
-							//TODO: Handle session ID, in particular sorting
-							//with session ID instead of timestamp
+							//See `SortMode::Session` for sorting by session ID
 							if let Ok(parsed_session_id) = value_str.parse::<u32>() {
 								self.log_entry.custom_fields.insert(
 									std::borrow::Cow::Borrowed("SessionId"),
@@ -1,6 +1,10 @@
+use super::super::message_arena::MessageArenaBuilder;
 use super::super::model;
+use super::super::model_internal::LogSourceExt;
 use super::glog;
+use super::rds_log;
 use super::sfile;
+use super::sfile_layout::SfileLayout;
 
 use std::fs::File;
 
@@ -9,6 +13,7 @@ pub enum LogParseError {
 	IoError(std::io::Error),
 	UnrecognizedFileExtension(std::ffi::OsString),
 	NoFileExtension,
+	InvalidPassword, //none of the passwords fits the encrypted sfile
 }
 
 impl std::error::Error for LogParseError {
@@ -28,6 +33,7 @@ impl std::fmt::Display for LogParseError {
 				write!(f, "Unrecognized file extension: {}", ext.to_string_lossy())
 			}
 			LogParseError::NoFileExtension => write!(f, "No file extension"),
+			LogParseError::InvalidPassword => write!(f, "Wrong or missing password"),
 		}
 	}
 }
@@ -38,7 +44,13 @@ impl From<std::io::Error> for LogParseError {
 	}
 }
 
-pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, LogParseError> {
+//Messages are moved into `messages`, see `LogSourceExt::from_source`
+pub fn from_file(
+	path: &std::path::PathBuf,
+	sfile_layout: &SfileLayout,
+	sfile_passwords: &[String],
+	messages: &mut MessageArenaBuilder,
+) -> Result<LogSourceExt, LogParseError> {
 	let extension = path.extension();
 	if let Some(extension) = extension {
 		match extension.to_string_lossy().to_lowercase().as_ref() {
@@ -49,10 +61,16 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, LogParse
 					name: path.file_name().unwrap().to_string_lossy().to_string(),
 					children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
 				};
-				Ok(glog::to_log_entries(file, root))
+				Ok(LogSourceExt::from_source(
+					glog::to_log_entries(file, root),
+					messages,
+				))
 			}
 			// ../logfiles/logfile1.sfile
-			"sfile" | "lfile" => sfile::from_file(&path).map_err(LogParseError::IoError),
+			"sfile" | "lfile" => sfile::from_file(&path, sfile_layout, sfile_passwords)
+				.map(|root| LogSourceExt::from_source(root, messages)),
+			// ../logfiles/rds.log, memory-mapped if big as these can be several GB
+			"log" => rds_log::map_file(path, messages).map_err(LogParseError::IoError),
 			//TODO: Implement heuristic, more file types
 			_ => Err(LogParseError::UnrecognizedFileExtension(
 				extension.to_os_string(),
@@ -3,8 +3,10 @@ pub mod io; //Central hub for log parser io
 pub mod rds_log;
 pub mod scanlib_log;
 pub mod sfile;
+pub mod sfile_layout;
 pub mod xlog;
 
 pub mod datetime_utils;
 
 pub use self::io::from_file;
+pub use self::io::LogParseError;
@@ -1,4 +1,6 @@
+use super::super::message_arena::{FileContents, MessageArenaBuilder};
 use super::super::model;
+use super::super::model_internal::{LogEntryExt, LogSourceExt, Timestamp, VISIBLE_ON};
 
 extern crate chrono;
 use chrono::{DateTime, NaiveDateTime, Utc};
@@ -316,6 +318,135 @@ pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) ->
 	root
 }
 
+// Memory-mapped RDS log for files too big to be parsed into memory. Messages
+// stay in the file (see `MessageArenaBuilder::map`), the store only keeps
+// their position along with timestamp, severity and source of each entry.
+// Smaller files are read into memory the same way, which avoids the
+// restrictions of memory-mapped files (see `FileContents::Mapped`).
+
+const MAP_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+struct MappedEntry {
+	timestamp: chrono::DateTime<Utc>,
+	severity: model::LogLevel,
+	message: (usize, usize), //start and end in the file
+}
+
+pub fn map_file(
+	path: &std::path::Path,
+	messages: &mut MessageArenaBuilder,
+) -> Result<LogSourceExt> {
+	let mut file = std::fs::File::open(path)?;
+	let contents = if file.metadata()?.len() < MAP_MIN_SIZE {
+		let mut data = Vec::new();
+		std::io::Read::read_to_end(&mut file, &mut data)?;
+		FileContents::Memory(data.into_boxed_slice())
+	} else {
+		//Safety: Truncating the file while it is mapped is not supported, see
+		//`FileContents::Mapped`. Truncation is detected by `MessageArena::check_file`, but
+		//only before drawing and searching, so a crash is still possible in between.
+		let mmap = unsafe { memmap2::Mmap::map(&file)? };
+		log::info!("Memory-mapped {:?} ({} bytes)", path, mmap.len());
+		FileContents::Mapped(mmap, file)
+	};
+	let mapped_entries = scan_entries(&contents);
+	messages.map(contents);
+	let entries = mapped_entries
+		.into_iter()
+		.map(|entry| LogEntryExt {
+			timestamp: Timestamp::from_datetime(&entry.timestamp),
+			severity: entry.severity,
+			message: messages.map_span(entry.message.0, entry.message.1),
+			source_id: 0,
+			custom_fields: Box::new([]),
+			visible: VISIBLE_ON,
+			entry_id: 0,
+			prev_offset: 0,
+			next_offset: 0,
+		})
+		.collect();
+	let name = path
+		.file_name()
+		.map(|name| name.to_string_lossy().to_string())
+		.unwrap_or_default();
+	Ok(LogSourceExt::from_entries(name, entries))
+}
+
+//Same format as `to_log_entries`, but lines are split up front, which is only possible in memory
+fn scan_entries(data: &[u8]) -> Vec<MappedEntry> {
+	let mut entries = Vec::<MappedEntry>::new();
+	let mut line_start = 0;
+	while line_start < data.len() {
+		let line_end = data[line_start..]
+			.iter()
+			.position(|byte| *byte == b'\n')
+			.map_or(data.len(), |pos| line_start + pos);
+		let mut content_end = line_end;
+		if content_end > line_start && data[content_end - 1] == b'\r' {
+			content_end -= 1;
+		}
+		match parse_header(&data[line_start..content_end]) {
+			Some((timestamp, severity, message_start)) => entries.push(MappedEntry {
+				timestamp,
+				severity,
+				message: (line_start + message_start, content_end),
+			}),
+			//Some log messages contain line breaks
+			None => match entries.last_mut() {
+				Some(entry) => entry.message.1 = content_end,
+				None => {
+					let default_entry = model::LogEntry {
+						..Default::default()
+					};
+					entries.push(MappedEntry {
+						timestamp: default_entry.timestamp,
+						severity: default_entry.severity,
+						message: (line_start, content_end),
+					});
+				}
+			},
+		}
+		line_start = line_end + 1;
+	}
+	entries
+}
+
+//Timestamp, severity and start of the message if `line` starts a new entry, i.e.
+//`datetime|[error code|]severity|log source|message`
+fn parse_header(line: &[u8]) -> Option<(DateTime<Utc>, model::LogLevel, usize)> {
+	let mut pos = 0;
+	let timestamp = parse_rds_datetime(next_field(line, &mut pos)?)?;
+	let mut severity = model::LogEntry {
+		..Default::default()
+	}
+	.severity;
+	if let Some(field) = next_field(line, &mut pos) {
+		match RdsLogSeverity::from_str(field) {
+			Some(rds_log_sev) => severity = normalize_rds_log_sev(rds_log_sev),
+			None => {
+				//Error code, the severity follows
+				match next_field(line, &mut pos).and_then(RdsLogSeverity::from_str) {
+					Some(rds_log_sev) => severity = normalize_rds_log_sev(rds_log_sev),
+					None => log::warn!(
+						"MALFORMED Log severity in: {}",
+						String::from_utf8_lossy(line)
+					),
+				}
+			}
+		}
+		//Log source; discard it for now
+		next_field(line, &mut pos);
+	}
+	Some((timestamp, severity, pos))
+}
+
+fn next_field<'a>(line: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+	let len = line[*pos..].iter().position(|byte| *byte == b'|')?;
+	let field = std::str::from_utf8(&line[*pos..*pos + len]).ok()?;
+	*pos += len + 1;
+	Some(field)
+}
+
 enum RdsLogParserState {
 	ExpectDatetime,
 	// Some log files contain an error code.
@@ -367,3 +498,41 @@ impl RdsLogSeverity {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_scan_entries() {
+		let data = b"2020-12-01 15:46:19.122|Info|Rds|First\r\n\
+			2020-12-01 15:46:20.000|  42|Error|Rds|Second\n\
+			continued | with a pipe\n\
+			2020-12-01 15:46:21.5|debug|Jobs|Third";
+		let root = to_log_entries(
+			&data[..],
+			model::LogSource {
+				name: String::new(),
+				children: model::LogSourceContents::Entries(Vec::new()),
+			},
+		);
+		let parsed = match root.children {
+			model::LogSourceContents::Entries(entries) => entries,
+			model::LogSourceContents::Sources(_) => unreachable!(),
+		};
+		let mapped = scan_entries(data);
+		assert_eq!(mapped.len(), 3);
+		assert_eq!(parsed.len(), mapped.len());
+		for (parsed, mapped) in parsed.iter().zip(&mapped) {
+			assert_eq!(parsed.timestamp, mapped.timestamp);
+			assert_eq!(parsed.severity, mapped.severity);
+			let message = std::str::from_utf8(&data[mapped.message.0..mapped.message.1]).unwrap();
+			assert_eq!(parsed.message, message);
+		}
+		let second = &mapped[1].message;
+		assert_eq!(
+			&data[second.0..second.1],
+			b"Second\ncontinued | with a pipe"
+		);
+	}
+}
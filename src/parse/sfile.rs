@@ -7,20 +7,33 @@ use chrono::prelude::Utc;
 use std::path::PathBuf;
 
 use super::super::model;
-use super::datetime_utils;
 use super::glog;
+use super::io::LogParseError;
 use super::rds_log;
 use super::scanlib_log;
+use super::sfile_layout::SfileLayout;
 use super::xlog;
 
 use std::collections::HashMap;
 use std::mem;
 
+//Tried after the passwords passed to `from_file`
 static SFILE_PASSWORD: Option<&'static str> = option_env!("SFILE_PASSWORD");
 
-pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io::Error> {
+//Encrypted members are decrypted with the first of `passwords` that fits
+pub fn from_file(
+	path: &std::path::PathBuf,
+	layout: &SfileLayout,
+	passwords: &[String],
+) -> Result<model::LogSource, LogParseError> {
 	let file = std::fs::File::open(&path)?;
-	let mut archive = zip::ZipArchive::new(file)?;
+	let mut archive = zip::ZipArchive::new(file).map_err(std::io::Error::from)?;
+	let candidates: Vec<&str> = passwords
+		.iter()
+		.map(|password| password.as_str())
+		.chain(SFILE_PASSWORD)
+		.collect();
+	let password = find_password(&mut archive, &candidates)?;
 
 	let mut glog_files = Vec::new();
 
@@ -31,11 +44,7 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io:
 	child_sources.reserve(archive.len());
 
 	for i in 0..archive.len() {
-		let file = if let Some(password) = SFILE_PASSWORD {
-			archive.by_index_decrypt(i, password.as_bytes())?.unwrap() //TODO: 21.06.2020: Handle InvalidPassword!
-		} else {
-			archive.by_index(i)?
-		};
+		let file = open_member(&mut archive, i, password)?;
 		let outpath = PathBuf::from(file.name());
 		let stem = outpath.file_stem().unwrap();
 		let stem = stem.to_string_lossy();
@@ -220,7 +229,7 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io:
 		if last_group != file.group_name {
 			if !deque.is_empty() {
 				let deque = mem::replace(&mut deque, std::collections::VecDeque::new());
-				let reader = ConcatZipReader::new(&mut archive, deque);
+				let reader = ConcatZipReader::new(&mut archive, password, deque);
 				let root = model::LogSource {
 					name: last_group,
 					children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
@@ -237,7 +246,7 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io:
 	}
 	if !deque.is_empty() {
 		let deque = mem::replace(&mut deque, std::collections::VecDeque::new());
-		let reader = ConcatZipReader::new(&mut archive, deque);
+		let reader = ConcatZipReader::new(&mut archive, password, deque);
 		let root = model::LogSource {
 			name: last_group,
 			children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
@@ -245,132 +254,71 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io:
 		child_sources.push(glog::to_log_entries(reader, root));
 	}
 
-	let mut contr_child_sources = Vec::new();
-	let mut sensor_child_sources: std::vec::Vec<model::LogSource> = Vec::new();
-	let mut cbox_child_sources = Vec::new();
-	let mut probe_child_sources = Vec::new();
+	//Glog groups sorted into the categories of the layout
+	let mut category_sources: Vec<Vec<model::LogSource>> =
+		layout.categories.iter().map(|_| Vec::new()).collect();
 	let mut unknown_child_sources = Vec::new();
 
 	for mut source in child_sources {
-		//Controller logs
-		if source.name.starts_with("contr_") {
-			//Remove "contr_" to make it look nicer
-			source.name = source.name.split_off(6);
-			contr_child_sources.push(source);
-			continue;
-		}
-
-		//Sensor logs
-		let mut iter = source.name.splitn(2, '_');
-		let board_name = iter.next().unwrap(); //First element always exists
-		if let Some(log_name) = iter.next() {
-			if board_name == "axis" ||
-				board_name == "sensorbase" ||
-				board_name == "telescope" ||
-				board_name == "trigger" ||
-				board_name == "adm" || // G
-				board_name == "laseroven" || // G
-				board_name == "wfd"	|| // W
-				board_name == "dynamicadm" || // P
-				board_name == "icbpower" || // P
-				board_name.starts_with("cfm") || // L
-				board_name == "laserctl" || // L
-				board_name == "wlanmodule" || // L
-				false
-			{
-				if sensor_child_sources.is_empty()
-					|| sensor_child_sources.last().unwrap().name != board_name
-				{
-					let board_name_string = board_name.to_string();
-					source.name = log_name.to_string();
-					sensor_child_sources.push(model::LogSource {
-						name: board_name_string,
-						children: { model::LogSourceContents::Sources(vec![source]) },
-					});
-				} else {
-					source.name = log_name.to_string();
-					if let model::LogSourceContents::Sources(sources) =
-						&mut sensor_child_sources.last_mut().unwrap().children
-					{
-						sources.push(source)
-					} else {
-						//We only pushed model::LogSourceContents::Sources
-						unreachable!();
+		let placement = match layout.classify(&source.name) {
+			Some(placement) => placement,
+			None => {
+				unknown_child_sources.push(source);
+				continue;
+			}
+		};
+		source.name = placement.name;
+		let sources = &mut category_sources[placement.category];
+		match placement.group {
+			Some(group) => {
+				//e.g. one node per sensor board
+				let node = match sources.iter().position(|node| node.name == group) {
+					Some(idx) => &mut sources[idx],
+					None => {
+						sources.push(model::LogSource {
+							name: group,
+							children: { model::LogSourceContents::Sources(Vec::new()) },
+						});
+						sources.last_mut().unwrap()
 					}
+				};
+				if let model::LogSourceContents::Sources(node_sources) = &mut node.children {
+					node_sources.push(source);
+				} else {
+					//We only pushed model::LogSourceContents::Sources
+					unreachable!();
 				}
-				continue;
 			}
+			None => sources.push(source),
 		}
-
-		//Connect Box logs
-		if source.name.starts_with("connectbox_") {
-			//Remove "connectbox_" to make it look nicer
-			source.name = source.name.split_off(11);
-			cbox_child_sources.push(source);
-			continue;
-		}
-
-		//Probe logs
-		if source.name.starts_with("ap21_") {
-			//Remove "ap21_" to make it look nicer
-			source.name = source.name.split_off(5);
-			probe_child_sources.push(source);
-			continue;
-		}
-
-		//Unknown logs
-		unknown_child_sources.push(source);
 	}
 
 	//Case insensitive sort by log source name
-	contr_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	sensor_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+	for sources in &mut category_sources {
+		sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+	}
 	client_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	cbox_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	probe_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
 	rds_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
 	scanlib_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
 	unknown_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
 
-	for mut source in &mut sensor_child_sources {
-		adjust_sensor_timestamps(&mut source);
-	}
-	for mut source in &mut cbox_child_sources {
-		adjust_sensor_timestamps(&mut source);
-	}
-	for mut source in &mut probe_child_sources {
-		adjust_sensor_timestamps(&mut source);
-	}
+	//Boot-relative timestamps of categories with `correct_timestamps` are corrected
+	//once the store is assembled, see `correction::default_rules`
 
-	let contr_logs = model::LogSource {
-		name: "Controller".to_string(),
-		children: { model::LogSourceContents::Sources(contr_child_sources) },
-	};
-	let sensor_logs = model::LogSource {
-		name: "Sensor".to_string(),
-		children: { model::LogSourceContents::Sources(sensor_child_sources) },
-	};
 	let client_logs = model::LogSource {
 		name: "Client".to_string(),
 		children: { model::LogSourceContents::Sources(client_child_sources) },
 	};
 
-	let mut sources_vec = vec![client_logs, contr_logs, sensor_logs];
+	let mut sources_vec = vec![client_logs];
 
-	if !cbox_child_sources.is_empty() {
-		let cbox_logs = model::LogSource {
-			name: "Connect Box".to_string(),
-			children: { model::LogSourceContents::Sources(cbox_child_sources) },
-		};
-		sources_vec.push(cbox_logs);
-	}
-
-	if !probe_child_sources.is_empty() {
-		let probe_logs = model::LogSource {
-			name: "Probe".to_string(),
-			children: { model::LogSourceContents::Sources(probe_child_sources) },
-		};
-		sources_vec.push(probe_logs);
+	for (category, sources) in layout.categories.iter().zip(category_sources) {
+		if category.show_empty || !sources.is_empty() {
+			sources_vec.push(model::LogSource {
+				name: category.name.clone(),
+				children: { model::LogSourceContents::Sources(sources) },
+			});
+		}
 	}
 
 	if !rds_child_sources.is_empty() {
@@ -403,134 +351,64 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io:
 	})
 }
 
-fn adjust_sensor_timestamps(source: &mut model::LogSource) {
-	match &mut source.children {
-		model::LogSourceContents::Sources(v) => {
-			for mut source in v {
-				adjust_sensor_timestamps(&mut source);
-			}
+//Password of the encrypted members, the first candidate that fits. None if no member is encrypted.
+//Only the first encrypted member is checked, an archive is encrypted with one password.
+fn find_password<'p, R: std::io::Read + std::io::Seek>(
+	archive: &mut zip::ZipArchive<R>,
+	candidates: &[&'p str],
+) -> Result<Option<&'p str>, LogParseError> {
+	for idx in 0..archive.len() {
+		if !is_encrypted(archive, idx)? {
+			continue;
 		}
-		model::LogSourceContents::Entries(v) => {
-			log::info!("Adjust sensor timestamps: {:?}", source.name);
+		return match candidates.iter().find(|password| {
+			matches!(
+				archive.by_index_decrypt(idx, password.as_bytes()),
+				Ok(Ok(_))
+			)
+		}) {
+			Some(password) => Ok(Some(*password)),
+			None => Err(LogParseError::InvalidPassword),
+		};
+	}
+	Ok(None)
+}
 
-			#[derive(Debug, PartialEq)]
-			struct Correction {
-				session_id: u32,
-				delta: i64,
-			}
-			let mut active_correction: Option<Correction> = None;
-			// Reverse iterate, from the newest to the oldest entry
-			for entry in v.iter_mut().rev() {
-				if let Some(field) = entry.custom_fields.get("SessionId") {
-					if let model::CustomField::UInt32(session_id) = field {
-						// Some log entries say:
-						// Setting EtherCAT time [delta = 1562060032100954112 ns].
-						// Others say (omitting the dot):
-						// Setting EtherCAT time [delta = 1562060032100954112 ns]
-						// We need to handle both...
-						if entry.message.starts_with("Setting EtherCAT time [delta = ")
-							&& (entry.message.ends_with(" ns].") || entry.message.ends_with(" ns]"))
-						{
-							let delta = entry.message.split(' ').nth(5).unwrap(); //we can unwrap here because we verified the format above
-							if let Ok(delta) = delta.parse::<i64>() {
-								let old_correction = mem::replace(
-									&mut active_correction,
-									Some(Correction {
-										session_id: *session_id,
-										delta,
-									}),
-								);
-
-								let old_session_id_opt =
-									if let Some(old_correction) = &old_correction {
-										Some(old_correction.session_id)
-									} else {
-										None
-									};
-
-								if old_correction == active_correction {
-									log::warn!(
-										"Overwriting EtherCAT Time with same content! {:?}",
-										active_correction
-									);
-								} else if old_session_id_opt == Some(*session_id) {
-									log::warn!(
-										"Overwriting EtherCAT Time! Old: {:?}, New: {:?}",
-										old_correction,
-										active_correction
-									);
-								} else {
-									// This is the happy path for reading timestamp corrections.
-									// Happens when:
-									// - The very first correction is read
-									// - A valid correction is read after the last one was invalidated by e.g. a session change
-									// - A valid correction replaces a previous valid correction due to session change
-									//log::info!(
-									//	"Read fresh timestamp correction: {:?}",
-									//	active_correction
-									//);
-								}
-							} else {
-								log::warn!("could not parse EtherCAT timestamp {}", delta);
-								active_correction = None;
-							}
-						} else {
-							if let Some(correction) = &active_correction {
-								if *session_id == correction.session_id {
-									// Timestamps before 01-01-72 00:00:00.000000 are not realistic because the device did not exist back then.
-									// We can safely assume that these are relative timestamps that are not yet corrected with EtherCAT time.
-									// It is also reasonable to assume that a device receives its EtherCAT time within 2 years (or never).
-									if entry.timestamp
-										< DateTime::<Utc>::from_utc(
-											NaiveDateTime::from_timestamp_opt(63_072_000, 0)
-												.unwrap(),
-											Utc,
-										) {
-										//Divide delta by 100 to convert from 1ns to 100ns ticks, which is the default GCOM timespan measurement
-										if let Some(corrected_timestamp) =
-											datetime_utils::add_offset_100ns(
-												entry.timestamp,
-												correction.delta / 100,
-											) {
-											entry.timestamp = corrected_timestamp;
-										} else {
-											log::warn!(
-												"could not correct timestamp with offset: {}",
-												correction.delta
-											);
-										}
-									}
-								} else {
-									// We moved on to a different session. Scrap active timestamp correction.
-									active_correction = None;
-								}
-							} else {
-								// This either happens if we encounter an already corrected timestamp and haven't yet
-								// encountered the log entry that specifies the time delta.
-								// Or else, it happens if the bus never connected, so the device never got the EtherCAT offset.
-								// This second case is also a normal thing to occur over the lifetime of a device,
-								// but we have to think about how to sort these log lines as their timestamp remains around 1970.
-								//log::warn!("Could not find EtherCAT offset for {}!", entry.message);
-							}
-						}
-					} else {
-						panic!("Wrong type for session ID!");
-					}
-				} else {
-					// "sensorbase_BaseboardSpecialLogs_1_v.glog" lacks session ID, these logs are special
-					log::warn!(
-						"No session ID found for sensor log entry: {}",
-						entry.message
-					);
-					active_correction = None;
-				}
-			}
-		}
+fn is_encrypted<R: std::io::Read + std::io::Seek>(
+	archive: &mut zip::ZipArchive<R>,
+	idx: usize,
+) -> std::io::Result<bool> {
+	match archive.by_index(idx) {
+		Ok(_) => Ok(false),
+		Err(zip::result::ZipError::UnsupportedArchive(
+			zip::result::ZipError::PASSWORD_REQUIRED,
+		)) => Ok(true),
+		Err(err) => Err(err.into()),
+	}
+}
+
+//Unencrypted members are read as they are, even if there is a password
+fn open_member<'a, R: std::io::Read + std::io::Seek>(
+	archive: &'a mut zip::ZipArchive<R>,
+	idx: usize,
+	password: Option<&str>,
+) -> std::io::Result<zip::read::ZipFile<'a>> {
+	match password {
+		Some(password) if is_encrypted(archive, idx)? => archive
+			.by_index_decrypt(idx, password.as_bytes())?
+			.map_err(|_| {
+				std::io::Error::new(
+					std::io::ErrorKind::InvalidData,
+					format!("Wrong password for archive member {}", idx),
+				)
+			}),
+		_ => Ok(archive.by_index(idx)?),
 	}
 }
 
 struct ConcatZipReader<'a, R: std::io::Read + std::io::Seek> {
 	archive: &'a mut zip::ZipArchive<R>,
+	password: Option<String>, //see `find_password`
 	file: Option<zip::read::ZipFile<'a>>,
 	indices: std::collections::VecDeque<usize>,
 }
@@ -538,10 +416,12 @@ struct ConcatZipReader<'a, R: std::io::Read + std::io::Seek> {
 impl<'a, R: std::io::Read + std::io::Seek> ConcatZipReader<'a, R> {
 	fn new(
 		archive: &'a mut zip::ZipArchive<R>,
+		password: Option<&str>,
 		indices: std::collections::VecDeque<usize>,
 	) -> ConcatZipReader<'a, R> {
 		ConcatZipReader {
 			archive,
+			password: password.map(|password| password.to_string()),
 			file: None,
 			indices,
 		}
@@ -566,13 +446,7 @@ impl<'a, R: std::io::Read + std::io::Seek> std::io::Read for ConcatZipReader<'a,
 				match self.indices.pop_front() {
 					Some(idx) => {
 						//Need to open new file
-						let f = if let Some(password) = SFILE_PASSWORD {
-							self.archive
-								.by_index_decrypt(idx, password.as_bytes())?
-								.unwrap() //TODO: 21.06.2020: Handle InvalidPassword!
-						} else {
-							self.archive.by_index(idx)?
-						};
+						let f = open_member(self.archive, idx, self.password.as_deref())?;
 						unsafe {
 							//Due to the fact that file references archive and both are in the same struct,
 							//this cannot be done in safe Rust
@@ -632,3 +506,53 @@ fn get_group_name(s: &str) -> String {
 	s.push_str(storage_type);
 	s
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::{Read, Write};
+
+	//Archive with one stored member, optionally flagged as encrypted
+	fn archive(encrypted: bool) -> zip::ZipArchive<std::io::Cursor<Vec<u8>>> {
+		let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+		let options =
+			zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+		writer.start_file("contr_Hwa.glog", options).unwrap();
+		writer.write_all(b"not really encrypted data").unwrap();
+		let mut bytes = writer.finish().unwrap().into_inner();
+		if encrypted {
+			//General purpose flags of the central directory header, bit 0 is encryption
+			let central = bytes
+				.windows(4)
+				.position(|window| window == [0x50, 0x4b, 0x01, 0x02])
+				.unwrap();
+			bytes[central + 8] |= 1;
+		}
+		zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap()
+	}
+
+	#[test]
+	fn test_passwords() {
+		//Unencrypted members are read as they are, passwords or not
+		let mut plain = archive(false);
+		assert!(matches!(find_password(&mut plain, &["secret"]), Ok(None)));
+		let mut contents = String::new();
+		open_member(&mut plain, 0, Some("secret"))
+			.unwrap()
+			.read_to_string(&mut contents)
+			.unwrap();
+		assert_eq!(contents, "not really encrypted data");
+
+		let mut encrypted = archive(true);
+		assert!(is_encrypted(&mut encrypted, 0).unwrap());
+		assert!(matches!(
+			find_password(&mut encrypted, &[]),
+			Err(LogParseError::InvalidPassword)
+		));
+		assert!(matches!(
+			find_password(&mut encrypted, &["wrong", "also wrong"]),
+			Err(LogParseError::InvalidPassword)
+		));
+		assert!(open_member(&mut encrypted, 0, Some("wrong")).is_err());
+	}
+}
@@ -0,0 +1,194 @@
+extern crate toml;
+
+// Categories of the sfile tree that glog groups are sorted into, see
+// `sfile_layout.toml` for the format. The built-in layout can be replaced
+// by a user file, so new board types do not need a code change.
+
+const DEFAULT_LAYOUT: &str = include_str!("sfile_layout.toml");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+	pub name: String,
+	pub prefixes: Vec<String>, //`*` matches anything but `_`
+	pub strip_prefix: bool,
+	pub group_by_prefix: bool, //one node per matched prefix, without trailing `_`
+	pub show_empty: bool,
+	pub correct_timestamps: bool, //see `correction::default_rules`
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SfileLayout {
+	pub categories: Vec<Category>,
+}
+
+//Where a glog group goes, see `SfileLayout::classify`
+#[derive(Debug, PartialEq)]
+pub struct Placement {
+	pub category: usize,
+	pub group: Option<String>, //node below the category, see `Category::group_by_prefix`
+	pub name: String,
+}
+
+impl Default for SfileLayout {
+	fn default() -> Self {
+		SfileLayout::from_toml(DEFAULT_LAYOUT).expect("Built-in sfile layout is valid")
+	}
+}
+
+impl SfileLayout {
+	//The user file at `path` if there is one, otherwise the built-in layout
+	pub fn load(path: &std::path::Path) -> SfileLayout {
+		let contents = match std::fs::read_to_string(path) {
+			Ok(contents) => contents,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+				return SfileLayout::default()
+			}
+			Err(err) => {
+				log::warn!("Could not load sfile layout {:?}: {}", path, err);
+				return SfileLayout::default();
+			}
+		};
+		match SfileLayout::from_toml(&contents) {
+			Ok(layout) => {
+				log::info!("Using sfile layout {:?}", path);
+				layout
+			}
+			Err(err) => {
+				log::warn!("Malformed sfile layout {:?}: {}", path, err);
+				SfileLayout::default()
+			}
+		}
+	}
+
+	//Categories without a name are skipped, missing flags are false
+	pub fn from_toml(contents: &str) -> Result<SfileLayout, toml::de::Error> {
+		let table = contents.parse::<toml::Table>()?;
+		let categories = table
+			.get("category")
+			.and_then(|v| v.as_array())
+			.map_or(&[][..], |v| v.as_slice())
+			.iter()
+			.filter_map(|category| {
+				let category = category.as_table()?;
+				let flag = |name: &str| {
+					category
+						.get(name)
+						.and_then(|v| v.as_bool())
+						.unwrap_or(false)
+				};
+				Some(Category {
+					name: category.get("name")?.as_str()?.to_string(),
+					prefixes: category
+						.get("prefixes")
+						.and_then(|v| v.as_array())
+						.map_or(&[][..], |v| v.as_slice())
+						.iter()
+						.filter_map(|prefix| prefix.as_str())
+						.map(|prefix| prefix.to_string())
+						.collect(),
+					strip_prefix: flag("strip_prefix"),
+					group_by_prefix: flag("group_by_prefix"),
+					show_empty: flag("show_empty"),
+					correct_timestamps: flag("correct_timestamps"),
+				})
+			})
+			.collect();
+		Ok(SfileLayout { categories })
+	}
+
+	//Category of the first matching prefix; None for unknown logs
+	pub fn classify(&self, group_name: &str) -> Option<Placement> {
+		self.categories
+			.iter()
+			.enumerate()
+			.find_map(|(idx, category)| {
+				let len = category
+					.prefixes
+					.iter()
+					.find_map(|prefix| match_prefix(prefix, group_name))?;
+				let (prefix, rest) = group_name.split_at(len);
+				Some(Placement {
+					category: idx,
+					group: category
+						.group_by_prefix
+						.then(|| prefix.trim_end_matches('_').to_string()),
+					name: if category.strip_prefix || category.group_by_prefix {
+						rest.to_string()
+					} else {
+						group_name.to_string()
+					},
+				})
+			})
+	}
+
+	pub fn corrected_categories(&self) -> Vec<&str> {
+		self.categories
+			.iter()
+			.filter(|category| category.correct_timestamps)
+			.map(|category| category.name.as_str())
+			.collect()
+	}
+}
+
+//Length of the start of `name` matched by `pattern`, where `*` matches anything but `_`
+fn match_prefix(pattern: &str, name: &str) -> Option<usize> {
+	let mut parts = pattern.split('*');
+	let first = parts.next().unwrap_or(""); //First element always exists
+	let mut len = name.strip_prefix(first).map(|_| first.len())?;
+	for part in parts {
+		let rest = &name[len..];
+		let skipped = rest.find('_').unwrap_or(rest.len());
+		//Shortest run of the wildcard that is followed by `part`
+		len += (0..=skipped)
+			.filter(|skip| rest.is_char_boundary(*skip))
+			.find(|skip| rest[*skip..].starts_with(part))?
+			+ part.len();
+	}
+	Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_classify() {
+		let layout = SfileLayout::default();
+		let placement = |category: usize, group: Option<&str>, name: &str| {
+			Some(Placement {
+				category,
+				group: group.map(|group| group.to_string()),
+				name: name.to_string(),
+			})
+		};
+		assert_eq!(layout.classify("contr_Hwa"), placement(0, None, "Hwa"));
+		assert_eq!(
+			layout.classify("axis_Motion_v"),
+			placement(1, Some("axis"), "Motion_v")
+		);
+		assert_eq!(
+			layout.classify("cfm12_Io"),
+			placement(1, Some("cfm12"), "Io")
+		);
+		assert_eq!(layout.classify("connectbox_Net"), placement(2, None, "Net"));
+		assert_eq!(layout.classify("ap21_Probe"), placement(3, None, "Probe"));
+		assert_eq!(layout.classify("axis"), None);
+		assert_eq!(layout.classify("axisfoo_Bar"), None);
+		assert_eq!(layout.classify("unknown_Log"), None);
+		assert_eq!(
+			layout.corrected_categories(),
+			vec!["Sensor", "Connect Box", "Probe"]
+		);
+
+		let layout = SfileLayout::from_toml(
+			"[[category]]\nname = 'Gripper'\nprefixes = ['grip*x_']\ncorrect_timestamps = true",
+		)
+		.unwrap();
+		assert_eq!(
+			layout.classify("grip2x_Log"),
+			placement(0, None, "grip2x_Log")
+		);
+		assert_eq!(layout.classify("grip_x_Log"), None);
+		assert_eq!(layout.corrected_categories(), vec!["Gripper"]);
+	}
+}
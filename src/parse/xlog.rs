@@ -44,7 +44,18 @@ pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) ->
 						}
 					}
 					"<M>" => {
-						log_entry.message = unit_value.to_string();
+						log_entry.message = unit_value.replace('˪', "\n");
+					}
+					"<I>" => {
+						if let Ok(process_id) = unit_value.parse::<u32>() {
+							log_entry.custom_fields.insert(
+								std::borrow::Cow::Borrowed("PID"),
+								model::CustomField::UInt32(process_id),
+							);
+						} else {
+							//TODO: Notify of malformed process ID?
+							log::warn!("MALFORMED Process ID: {}", unit_value);
+						}
 					}
 					_ => {
 						//TODO: Notify of invalid kind?
@@ -0,0 +1,108 @@
+extern crate keyring;
+extern crate serde_json;
+
+use crate::parse::LogParseError;
+
+// Remembered sfile passwords, kept in the keyring of the operating system
+// (Secret Service on Linux, Credential Manager on Windows, Keychain on macOS)
+// instead of the settings file. All passwords are stored in one entry as a
+// JSON array, as keyrings cannot list the entries of an application.
+
+const SERVICE: &str = "sherlog";
+const SFILE_USER: &str = "sfile passwords";
+
+fn sfile_entry() -> keyring::Result<keyring::Entry> {
+	keyring::Entry::new(SERVICE, SFILE_USER)
+}
+
+//Tried on encrypted sfiles, see `sfile::from_file`. Empty if there are none or the keyring is unavailable.
+pub fn sfile_passwords() -> Vec<String> {
+	match sfile_entry().and_then(|entry| entry.get_password()) {
+		Ok(passwords) => serde_json::from_str(&passwords).unwrap_or_else(|err| {
+			log::warn!("Malformed sfile passwords in keyring: {}", err);
+			Vec::new()
+		}),
+		Err(keyring::Error::NoEntry) => Vec::new(),
+		Err(err) => {
+			log::warn!("Could not read sfile passwords from keyring: {}", err);
+			Vec::new()
+		}
+	}
+}
+
+pub fn remember_sfile_password(password: &str) -> keyring::Result<()> {
+	let mut passwords = sfile_passwords();
+	if passwords.iter().any(|known| known == password) {
+		return Ok(());
+	}
+	passwords.push(password.to_string());
+	//Serializing a list of strings cannot fail
+	sfile_entry()?.set_password(&serde_json::to_string(&passwords).unwrap())
+}
+
+//Parses with the candidate passwords and asks for another one with `prompt` as long as they are
+//rejected. A password the user wants remembered is only passed to `remember` once it opened the
+//file, so that mistyped passwords do not end up in the keyring.
+pub fn try_passwords<T>(
+	mut candidates: Vec<String>,
+	mut parse: impl FnMut(&[String]) -> Result<T, LogParseError>,
+	mut prompt: impl FnMut() -> Option<(String, bool)>,
+	mut remember: impl FnMut(&str),
+) -> Result<T, LogParseError> {
+	let mut pending: Option<String> = None;
+	loop {
+		let result = parse(&candidates);
+		match result {
+			Err(LogParseError::InvalidPassword) => (),
+			Ok(_) => {
+				if let Some(password) = pending {
+					remember(&password);
+				}
+				return result;
+			}
+			Err(_) => return result,
+		}
+		match prompt() {
+			Some((password, remember_password)) => {
+				pending = remember_password.then(|| password.clone());
+				candidates = vec![password];
+			}
+			None => return result,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_try_passwords() {
+		let parse = |candidates: &[String]| match candidates.iter().any(|p| p == "right") {
+			true => Ok(()),
+			false => Err(LogParseError::InvalidPassword),
+		};
+		let mut entered = vec![("right", true), ("wrong", true)];
+		let mut remembered = Vec::new();
+		let result = try_passwords(
+			vec!["old".to_string()],
+			parse,
+			|| entered.pop().map(|(p, remember)| (p.to_string(), remember)),
+			|password| remembered.push(password.to_string()),
+		);
+		assert!(result.is_ok());
+		assert_eq!(remembered, vec!["right"]);
+
+		//Rejected and then cancelled
+		let mut entered = vec![("wrong", true)];
+		let mut remembered = Vec::new();
+		let result = try_passwords(
+			Vec::new(),
+			parse,
+			|| entered.pop().map(|(p, remember)| (p.to_string(), remember)),
+			|password| remembered.push(password.to_string()),
+		);
+		assert!(matches!(result, Err(LogParseError::InvalidPassword)));
+		assert!(remembered.is_empty());
+	}
+}
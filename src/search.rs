@@ -0,0 +1,158 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+
+use crate::message_arena::MessageArena;
+
+// Text search on worker threads, so the GUI stays responsive while typing into
+// the search entry of a huge store. The candidates are split into chunks which
+// the workers take one after another. Matches are collected per chunk and only
+// applied to the store once all chunks are done, see
+// `LogStoreLinear::poll_search`. Dropping the job cancels it.
+
+//Searches with fewer candidates are applied right away, they do not freeze the GUI
+pub const BACKGROUND_MIN_ENTRIES: usize = 200_000;
+const CHUNK_SIZE: usize = 16_384;
+
+struct Shared {
+	text: String,
+	messages: Arc<MessageArena>,
+	message_ids: Vec<u32>, //messages of the candidates, same order
+	next_chunk: AtomicUsize,
+	searched: AtomicUsize,
+	matched: AtomicUsize,
+	cancelled: AtomicBool,
+}
+
+pub struct SearchJob {
+	pub text: String,
+	pub candidates: Vec<u32>, //offsets to search, ascending
+	shared: Arc<Shared>,
+	results: mpsc::Receiver<(usize, Vec<u32>)>, //chunk index and matching candidate indices
+	chunks: Vec<Option<Vec<u32>>>,
+	chunks_done: usize,
+}
+
+impl SearchJob {
+	pub fn start(
+		text: &str,
+		candidates: Vec<u32>,
+		messages: Arc<MessageArena>,
+		message_ids: Vec<u32>,
+	) -> SearchJob {
+		let chunk_count = candidates.len().div_ceil(CHUNK_SIZE);
+		let shared = Arc::new(Shared {
+			text: text.to_string(),
+			messages,
+			message_ids,
+			next_chunk: AtomicUsize::new(0),
+			searched: AtomicUsize::new(0),
+			matched: AtomicUsize::new(0),
+			cancelled: AtomicBool::new(false),
+		});
+		let (sender, results) = mpsc::channel();
+		let workers = std::thread::available_parallelism()
+			.map(|threads| threads.get())
+			.unwrap_or(1)
+			.min(chunk_count);
+		for _ in 0..workers {
+			let shared = shared.clone();
+			let sender = sender.clone();
+			std::thread::spawn(move || search_chunks(&shared, chunk_count, &sender));
+		}
+		SearchJob {
+			text: text.to_string(),
+			candidates,
+			shared,
+			results,
+			chunks: vec![None; chunk_count],
+			chunks_done: 0,
+		}
+	}
+
+	//Searched candidates, all candidates and matches found so far
+	pub fn progress(&self) -> (usize, usize, usize) {
+		(
+			self.shared.searched.load(Ordering::Relaxed),
+			self.candidates.len(),
+			self.shared.matched.load(Ordering::Relaxed),
+		)
+	}
+
+	//Offsets of the matches (ascending) once all chunks are done
+	pub fn poll(&mut self) -> Option<Vec<u32>> {
+		while let Ok((chunk, matches)) = self.results.try_recv() {
+			self.chunks[chunk] = Some(matches);
+			self.chunks_done += 1;
+		}
+		if self.chunks_done < self.chunks.len() {
+			return None;
+		}
+		let candidates = &self.candidates;
+		Some(
+			self.chunks
+				.iter_mut()
+				.flat_map(|chunk| chunk.take().unwrap_or_default())
+				.map(|idx| candidates[idx as usize])
+				.collect(),
+		)
+	}
+}
+
+impl Drop for SearchJob {
+	fn drop(&mut self) {
+		self.shared.cancelled.store(true, Ordering::Relaxed);
+	}
+}
+
+fn search_chunks(shared: &Shared, chunk_count: usize, sender: &mpsc::Sender<(usize, Vec<u32>)>) {
+	loop {
+		let chunk = shared.next_chunk.fetch_add(1, Ordering::Relaxed);
+		if chunk >= chunk_count || shared.cancelled.load(Ordering::Relaxed) {
+			return;
+		}
+		shared.messages.check_file();
+		let start = chunk * CHUNK_SIZE;
+		let end = std::cmp::min(start + CHUNK_SIZE, shared.message_ids.len());
+		let matches: Vec<u32> = (start..end)
+			.filter(|idx| {
+				shared
+					.messages
+					.get(shared.message_ids[*idx])
+					.contains(shared.text.as_str())
+			})
+			.map(|idx| idx as u32)
+			.collect();
+		shared.searched.fetch_add(end - start, Ordering::Relaxed);
+		shared.matched.fetch_add(matches.len(), Ordering::Relaxed);
+		if sender.send((chunk, matches)).is_err() {
+			return; //Job was dropped
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_search_job() {
+		let mut messages = crate::message_arena::MessageArenaBuilder::default();
+		let message_ids: Vec<u32> = (0..100_000)
+			.map(|idx| messages.intern(&format!("message {}", idx)))
+			.collect();
+		let candidates: Vec<u32> = (0..100_000).map(|idx| idx * 2).collect();
+		let mut job = SearchJob::start("99", candidates, Arc::new(messages.finish()), message_ids);
+		let matches = loop {
+			if let Some(matches) = job.poll() {
+				break matches;
+			}
+			std::thread::yield_now();
+		};
+		let expected: Vec<u32> = (0..100_000u32)
+			.filter(|idx| idx.to_string().contains("99"))
+			.map(|idx| idx * 2)
+			.collect();
+		assert_eq!(matches, expected);
+		assert_eq!(job.progress(), (100_000, 100_000, expected.len()));
+	}
+}
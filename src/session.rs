@@ -0,0 +1,502 @@
+extern crate chrono;
+extern crate serde_json;
+
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::log_store;
+use crate::log_store::LogStoreLinear;
+use crate::model;
+use crate::model_internal::Timestamp;
+
+// Investigation session, persisted next to the log file as `<logfile>.sherlog.json`.
+// Entries and sources are referenced by stable keys instead of `LogStoreLinear`
+// offsets, as offsets change with every parser or sorting change.
+
+#[derive(Debug)]
+pub enum SessionError {
+	IoError(std::io::Error),
+	ParseError(serde_json::Error),
+}
+
+impl std::error::Error for SessionError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			SessionError::IoError(err) => Some(err),
+			SessionError::ParseError(err) => Some(err),
+		}
+	}
+}
+
+impl std::fmt::Display for SessionError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			SessionError::IoError(err) => write!(f, "{}", err),
+			SessionError::ParseError(err) => write!(f, "Malformed session file: {}", err),
+		}
+	}
+}
+
+impl From<std::io::Error> for SessionError {
+	fn from(error: std::io::Error) -> Self {
+		SessionError::IoError(error)
+	}
+}
+
+impl From<serde_json::Error> for SessionError {
+	fn from(error: serde_json::Error) -> Self {
+		SessionError::ParseError(error)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntryKey {
+	pub source: String, //source path without the root (file name), see `source_key`
+	pub timestamp: chrono::DateTime<Utc>, //as loaded, see `LogStoreLinear::key_timestamp`
+	pub message_hash: u64,
+}
+
+impl EntryKey {
+	pub fn from_entry(store: &LogStoreLinear, offset: usize) -> EntryKey {
+		let entry = &store.store[offset];
+		EntryKey {
+			source: store
+				.log_sources
+				.get(&entry.source_id)
+				.map(|path| source_key(path).to_string())
+				.unwrap_or_default(),
+			timestamp: store.key_timestamp(offset).to_datetime(),
+			message_hash: message_hash(store.message(offset)),
+		}
+	}
+
+	fn to_json(&self) -> serde_json::Value {
+		let mut table = serde_json::Map::new();
+		table.insert("source".to_string(), self.source.clone().into());
+		table.insert(
+			"timestamp".to_string(),
+			self.timestamp
+				.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+				.into(),
+		);
+		table.insert(
+			"message_hash".to_string(),
+			format!("{:016x}", self.message_hash).into(),
+		);
+		serde_json::Value::Object(table)
+	}
+
+	fn from_json(value: &serde_json::Value) -> Option<EntryKey> {
+		let table = value.as_object()?;
+		let source = table.get("source")?.as_str()?.to_string();
+		let timestamp = DateTime::parse_from_rfc3339(table.get("timestamp")?.as_str()?)
+			.ok()?
+			.with_timezone(&Utc);
+		let message_hash = u64::from_str_radix(table.get("message_hash")?.as_str()?, 16).ok()?;
+		Some(EntryKey {
+			source,
+			timestamp,
+			message_hash,
+		})
+	}
+}
+
+// FNV-1a: Unlike `DefaultHasher`, the result is guaranteed to be the same
+// across Rust versions and platforms, which is required to share sessions.
+pub fn message_hash(message: &str) -> u64 {
+	let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+	for byte in message.bytes() {
+		hash ^= u64::from(byte);
+		hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+	}
+	hash
+}
+
+// Source paths in `LogStoreLinear::log_sources` look like `/<file name>/Controller/Hwa`.
+// Strip the root so that sessions survive renaming the log file.
+pub fn source_key(path: &str) -> &str {
+	match path.get(1..).and_then(|rest| rest.find('/')) {
+		Some(offset) => &path[offset + 2..],
+		None => "",
+	}
+}
+
+pub fn session_path(log_path: &std::path::Path) -> std::path::PathBuf {
+	let mut file_name = log_path.as_os_str().to_os_string();
+	file_name.push(".sherlog.json");
+	std::path::PathBuf::from(file_name)
+}
+
+pub struct Session {
+	pub show_crit: bool,
+	pub show_err: bool,
+	pub show_warn: bool,
+	pub show_info: bool,
+	pub show_dbg: bool,
+	pub show_trace: bool,
+
+	pub hidden_sources: Vec<String>,                   //see `source_key`
+	pub source_colors: Vec<(String, (f64, f64, f64))>, //user-assigned colors, see `source_key`
+	pub search_text: String,
+	pub field_filter: Option<(String, model::CustomField)>, //see `LogStoreLinear::field_filter`
+	pub time_range: Option<(DateTime<Utc>, DateTime<Utc>)>, //see `LogStoreLinear::time_range`
+	pub sort_mode: log_store::SortMode,
+	pub place_boot_relative: bool, //see `LogStoreLinear::set_place_boot_relative`
+	pub corrections_enabled: bool, //see `LogStoreLinear::set_corrections_enabled`
+	pub anchor: Option<EntryKey>,
+	pub bookmarks: Vec<EntryKey>,
+}
+
+impl Session {
+	pub fn capture(
+		store: &LogStoreLinear,
+		hidden_sources: Vec<String>,
+		search_text: String,
+	) -> Session {
+		Session {
+			show_crit: store.show_crit,
+			show_err: store.show_err,
+			show_warn: store.show_warn,
+			show_info: store.show_info,
+			show_dbg: store.show_dbg,
+			show_trace: store.show_trace,
+
+			hidden_sources,
+			source_colors: store
+				.source_colors
+				.iter()
+				.filter_map(|(id, color)| {
+					store
+						.log_sources
+						.get(id)
+						.map(|path| (source_key(path).to_string(), *color))
+				})
+				.collect(),
+			search_text,
+			field_filter: store.field_filter.clone(),
+			time_range: store.time_range,
+			sort_mode: store.sort_mode,
+			place_boot_relative: store.place_boot_relative,
+			corrections_enabled: store.corrections_enabled,
+			anchor: store
+				.anchor_offset
+				.map(|offset| EntryKey::from_entry(store, offset)),
+			bookmarks: store
+				.bookmarks
+				.iter()
+				.map(|offset| EntryKey::from_entry(store, *offset))
+				.collect(),
+		}
+	}
+
+	//True if the session holds nothing worth persisting. The anchor alone does
+	//not count, as every click into the log view sets it. Severity visibility
+	//is compared to the default from the user settings.
+	pub fn is_default(&self, default_visibility: [bool; 6]) -> bool {
+		[
+			self.show_crit,
+			self.show_err,
+			self.show_warn,
+			self.show_info,
+			self.show_dbg,
+			self.show_trace,
+		] == default_visibility
+			&& self.hidden_sources.is_empty()
+			&& self.source_colors.is_empty()
+			&& self.search_text.is_empty()
+			&& self.field_filter.is_none()
+			&& self.time_range.is_none()
+			&& self.sort_mode == log_store::SortMode::Timestamp
+			&& !self.place_boot_relative
+			&& self.corrections_enabled
+			&& self.bookmarks.is_empty()
+	}
+
+	//Severities missing in the file get the default visibility from the user settings, see `is_default`
+	pub fn load(
+		path: &std::path::Path,
+		default_visibility: [bool; 6],
+	) -> Result<Session, SessionError> {
+		let contents = std::fs::read_to_string(path)?;
+		let value = serde_json::from_str::<serde_json::Value>(&contents)?;
+		Ok(Session::from_json(&value, default_visibility))
+	}
+
+	pub fn save(&self, path: &std::path::Path) -> Result<(), SessionError> {
+		std::fs::write(path, serde_json::to_string_pretty(&self.to_json())?)?;
+		Ok(())
+	}
+
+	fn to_json(&self) -> serde_json::Value {
+		let mut severity = serde_json::Map::new();
+		severity.insert("critical".to_string(), self.show_crit.into());
+		severity.insert("error".to_string(), self.show_err.into());
+		severity.insert("warning".to_string(), self.show_warn.into());
+		severity.insert("info".to_string(), self.show_info.into());
+		severity.insert("debug".to_string(), self.show_dbg.into());
+		severity.insert("trace".to_string(), self.show_trace.into());
+
+		let mut table = serde_json::Map::new();
+		table.insert("search_text".to_string(), self.search_text.clone().into());
+		table.insert(
+			"hidden_sources".to_string(),
+			serde_json::Value::Array(
+				self.hidden_sources
+					.iter()
+					.map(|source| source.clone().into())
+					.collect(),
+			),
+		);
+		table.insert("severity".to_string(), serde_json::Value::Object(severity));
+		table.insert("sort_mode".to_string(), self.sort_mode.id().into());
+		table.insert(
+			"place_boot_relative".to_string(),
+			self.place_boot_relative.into(),
+		);
+		table.insert(
+			"corrections_enabled".to_string(),
+			self.corrections_enabled.into(),
+		);
+		let mut source_colors = serde_json::Map::new();
+		for (source, color) in &self.source_colors {
+			source_colors.insert(source.clone(), log_store::color_to_hex(*color).into());
+		}
+		table.insert(
+			"source_colors".to_string(),
+			serde_json::Value::Object(source_colors),
+		);
+		if let Some((name, value)) = &self.field_filter {
+			table.insert(
+				"field_filter".to_string(),
+				serde_json::json!({
+					"name": name,
+					"type": custom_field_type(value),
+					"value": value.to_string(),
+				}),
+			);
+		}
+		if let Some((start, end)) = &self.time_range {
+			let format = |timestamp: &DateTime<Utc>| {
+				timestamp.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+			};
+			table.insert(
+				"time_range".to_string(),
+				serde_json::json!({ "start": format(start), "end": format(end) }),
+			);
+		}
+		if let Some(anchor) = &self.anchor {
+			table.insert("anchor".to_string(), anchor.to_json());
+		}
+		table.insert(
+			"bookmarks".to_string(),
+			serde_json::Value::Array(self.bookmarks.iter().map(EntryKey::to_json).collect()),
+		);
+		serde_json::Value::Object(table)
+	}
+
+	// Lenient: Missing or malformed values fall back to defaults, so that
+	// session files written by older or newer versions can still be opened.
+	fn from_json(value: &serde_json::Value, default_visibility: [bool; 6]) -> Session {
+		let table = value.as_object();
+		let get = |name: &str| table.and_then(|table| table.get(name));
+		let severity = get("severity").and_then(|v| v.as_object());
+		let show = |name: &str, default: bool| {
+			severity
+				.and_then(|severity| severity.get(name))
+				.and_then(|v| v.as_bool())
+				.unwrap_or(default)
+		};
+		Session {
+			show_crit: show("critical", default_visibility[0]),
+			show_err: show("error", default_visibility[1]),
+			show_warn: show("warning", default_visibility[2]),
+			show_info: show("info", default_visibility[3]),
+			show_dbg: show("debug", default_visibility[4]),
+			show_trace: show("trace", default_visibility[5]),
+
+			hidden_sources: get("hidden_sources")
+				.and_then(|v| v.as_array())
+				.map(|v| {
+					v.iter()
+						.filter_map(|source| source.as_str().map(|s| s.to_string()))
+						.collect()
+				})
+				.unwrap_or_default(),
+			source_colors: get("source_colors")
+				.and_then(|v| v.as_object())
+				.map(|v| {
+					v.iter()
+						.filter_map(|(source, color)| {
+							color
+								.as_str()
+								.and_then(log_store::color_from_hex)
+								.map(|color| (source.clone(), color))
+						})
+						.collect()
+				})
+				.unwrap_or_default(),
+			search_text: get("search_text")
+				.and_then(|v| v.as_str())
+				.unwrap_or_default()
+				.to_string(),
+			field_filter: get("field_filter").and_then(|v| {
+				let field_filter = v.as_object()?;
+				let string = |name: &str| field_filter.get(name).and_then(|v| v.as_str());
+				Some((
+					string("name")?.to_string(),
+					custom_field_from_str(string("type")?, string("value")?)?,
+				))
+			}),
+			time_range: get("time_range").and_then(|v| {
+				let time_range = v.as_object()?;
+				let timestamp = |name: &str| {
+					DateTime::parse_from_rfc3339(time_range.get(name)?.as_str()?)
+						.ok()
+						.map(|timestamp| timestamp.with_timezone(&Utc))
+				};
+				Some((timestamp("start")?, timestamp("end")?))
+			}),
+			sort_mode: get("sort_mode")
+				.and_then(|v| v.as_str())
+				.and_then(log_store::SortMode::from_id)
+				.unwrap_or(log_store::SortMode::Timestamp),
+			place_boot_relative: get("place_boot_relative")
+				.and_then(|v| v.as_bool())
+				.unwrap_or(false),
+			corrections_enabled: get("corrections_enabled")
+				.and_then(|v| v.as_bool())
+				.unwrap_or(true),
+			anchor: get("anchor").and_then(EntryKey::from_json),
+			bookmarks: get("bookmarks")
+				.and_then(|v| v.as_array())
+				.map(|v| v.iter().filter_map(EntryKey::from_json).collect())
+				.unwrap_or_default(),
+		}
+	}
+}
+
+fn custom_field_type(field: &model::CustomField) -> &'static str {
+	match field {
+		model::CustomField::Int64(_) => "Int64",
+		model::CustomField::Int32(_) => "Int32",
+		model::CustomField::Int16(_) => "Int16",
+		model::CustomField::Int8(_) => "Int8",
+		model::CustomField::UInt64(_) => "UInt64",
+		model::CustomField::UInt32(_) => "UInt32",
+		model::CustomField::UInt16(_) => "UInt16",
+		model::CustomField::UInt8(_) => "UInt8",
+		model::CustomField::Float32(_) => "Float32",
+		model::CustomField::Float64(_) => "Float64",
+		model::CustomField::String(_) => "String",
+	}
+}
+
+// The type is kept, as the field filter only matches values of the same type
+fn custom_field_from_str(field_type: &str, value: &str) -> Option<model::CustomField> {
+	Some(match field_type {
+		"Int64" => model::CustomField::Int64(value.parse().ok()?),
+		"Int32" => model::CustomField::Int32(value.parse().ok()?),
+		"Int16" => model::CustomField::Int16(value.parse().ok()?),
+		"Int8" => model::CustomField::Int8(value.parse().ok()?),
+		"UInt64" => model::CustomField::UInt64(value.parse().ok()?),
+		"UInt32" => model::CustomField::UInt32(value.parse().ok()?),
+		"UInt16" => model::CustomField::UInt16(value.parse().ok()?),
+		"UInt8" => model::CustomField::UInt8(value.parse().ok()?),
+		"Float32" => model::CustomField::Float32(value.parse().ok()?),
+		"Float64" => model::CustomField::Float64(value.parse().ok()?),
+		"String" => model::CustomField::String(value.to_string()),
+		_ => return None,
+	})
+}
+
+// Map entry keys to offsets in the store. Only hashes messages of entries whose
+// timestamp matches one of the keys, so this stays cheap even for huge stores.
+pub fn resolve_entries(store: &LogStoreLinear, keys: &[EntryKey]) -> Vec<Option<usize>> {
+	let timestamps: HashSet<Timestamp> = keys
+		.iter()
+		.map(|key| Timestamp::from_datetime(&key.timestamp))
+		.collect();
+	let mut offsets = HashMap::<EntryKey, usize>::new();
+	for offset in 0..store.store.len() {
+		if timestamps.contains(&store.key_timestamp(offset)) {
+			//In case of identical entries, the first one wins
+			offsets
+				.entry(EntryKey::from_entry(store, offset))
+				.or_insert(offset);
+		}
+	}
+	keys.iter().map(|key| offsets.get(key).copied()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_source_key() {
+		assert_eq!(
+			source_key("/logfile.sfile/Controller/Hwa"),
+			"Controller/Hwa"
+		);
+		assert_eq!(source_key("/logfile.sfile/Client"), "Client");
+		assert_eq!(source_key("/logfile.sfile"), "");
+		assert_eq!(source_key(""), "");
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let key = EntryKey {
+			source: "Controller/Hwa".to_string(),
+			timestamp: DateTime::parse_from_rfc3339("2020-06-26T15:38:19.085750200Z")
+				.unwrap()
+				.with_timezone(&Utc),
+			message_hash: message_hash("Setting EtherCAT time [delta = 1 ns]"),
+		};
+		let end = DateTime::parse_from_rfc3339("2020-06-26T15:40:00Z")
+			.unwrap()
+			.with_timezone(&Utc);
+		let session = Session {
+			show_crit: true,
+			show_err: true,
+			show_warn: false,
+			show_info: true,
+			show_dbg: false,
+			show_trace: true,
+			hidden_sources: vec!["Sensor".to_string(), "Client/Channel 1".to_string()],
+			source_colors: vec![("Controller/Hwa".to_string(), (1.0, 0.0, 0.2))],
+			search_text: "EtherCAT".to_string(),
+			field_filter: Some(("SessionId".to_string(), model::CustomField::UInt32(7))),
+			time_range: Some((key.timestamp, end)),
+			sort_mode: log_store::SortMode::Session,
+			place_boot_relative: true,
+			corrections_enabled: false,
+			anchor: Some(key.clone()),
+			bookmarks: vec![key.clone(), key],
+		};
+		let text = session.to_json().to_string();
+		let restored = Session::from_json(&serde_json::from_str(&text).unwrap(), [true; 6]);
+		assert!(!restored.show_warn && !restored.show_dbg && restored.show_trace);
+		assert_eq!(restored.hidden_sources, session.hidden_sources);
+		assert_eq!(restored.source_colors, session.source_colors);
+		assert_eq!(restored.search_text, session.search_text);
+		assert_eq!(restored.field_filter, session.field_filter);
+		assert_eq!(restored.time_range, session.time_range);
+		assert_eq!(restored.sort_mode, session.sort_mode);
+		assert!(restored.place_boot_relative && !restored.corrections_enabled);
+		assert_eq!(restored.anchor, session.anchor);
+		assert_eq!(restored.bookmarks, session.bookmarks);
+	}
+
+	#[test]
+	fn test_missing_severity() {
+		//Written before `trace` was known, debug and trace are hidden by default
+		let value = serde_json::json!({ "severity": { "critical": true, "debug": true } });
+		let default_visibility = [true, true, true, true, false, false];
+		let session = Session::from_json(&value, default_visibility);
+		assert!(session.show_crit && session.show_err && session.show_dbg);
+		assert!(!session.show_trace);
+		let session = Session::from_json(&serde_json::json!({}), default_visibility);
+		assert!(session.is_default(default_visibility));
+	}
+}
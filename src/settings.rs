@@ -0,0 +1,603 @@
+extern crate chrono;
+extern crate toml;
+
+use chrono::prelude::*;
+
+use crate::copy_format;
+use crate::correction;
+use crate::log_store;
+use crate::log_store::LogStoreLinear;
+use crate::theme;
+
+// User preferences, persisted as `sherlog/settings.toml` in the XDG config
+// directory (`%LOCALAPPDATA%` on Windows). Unlike sessions, they apply to
+// every log file.
+
+#[derive(Debug)]
+pub enum SettingsError {
+	IoError(std::io::Error),
+	ParseError(toml::de::Error),
+}
+
+impl std::error::Error for SettingsError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			SettingsError::IoError(err) => Some(err),
+			SettingsError::ParseError(err) => Some(err),
+		}
+	}
+}
+
+impl std::fmt::Display for SettingsError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			SettingsError::IoError(err) => write!(f, "{}", err),
+			SettingsError::ParseError(err) => write!(f, "Malformed settings file: {}", err),
+		}
+	}
+}
+
+impl From<std::io::Error> for SettingsError {
+	fn from(error: std::io::Error) -> Self {
+		SettingsError::IoError(error)
+	}
+}
+
+impl From<toml::de::Error> for SettingsError {
+	fn from(error: toml::de::Error) -> Self {
+		SettingsError::ParseError(error)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeZone {
+	Utc,
+	Local,
+	Fixed(chrono::FixedOffset),
+}
+
+impl TimeZone {
+	//`UTC`, `Local` or an offset like `+02:00`
+	pub fn parse(value: &str) -> Option<TimeZone> {
+		let value = value.trim();
+		if value.eq_ignore_ascii_case("utc") {
+			return Some(TimeZone::Utc);
+		}
+		if value.eq_ignore_ascii_case("local") {
+			return Some(TimeZone::Local);
+		}
+		let (sign, offset) = match value.as_bytes().first() {
+			Some(b'+') => (1, &value[1..]),
+			Some(b'-') => (-1, &value[1..]),
+			_ => return None,
+		};
+		let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+		let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+		chrono::FixedOffset::east_opt(sign * seconds).map(TimeZone::Fixed)
+	}
+}
+
+impl std::fmt::Display for TimeZone {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			TimeZone::Utc => write!(f, "UTC"),
+			TimeZone::Local => write!(f, "Local"),
+			TimeZone::Fixed(offset) => write!(f, "{}", offset),
+		}
+	}
+}
+
+pub fn format_timestamp(timestamp: &DateTime<Utc>, format: &str, time_zone: &TimeZone) -> String {
+	match time_zone {
+		TimeZone::Utc => timestamp.format(format).to_string(),
+		TimeZone::Local => timestamp.with_timezone(&Local).format(format).to_string(),
+		TimeZone::Fixed(offset) => timestamp.with_timezone(offset).format(format).to_string(),
+	}
+}
+
+//Inverse of `format_timestamp`, used for user input. Besides the given format,
+//ISO-like dates and plain times are accepted; the latter are taken on `reference_date`.
+pub fn parse_timestamp(
+	text: &str,
+	format: &str,
+	time_zone: &TimeZone,
+	reference_date: NaiveDate,
+) -> Option<DateTime<Utc>> {
+	use chrono::TimeZone as _;
+	let text = text.trim();
+	let naive = [format, "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"]
+		.iter()
+		.find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+		.or_else(|| {
+			["%H:%M:%S%.f", "%H:%M"]
+				.iter()
+				.find_map(|format| NaiveTime::parse_from_str(text, format).ok())
+				.map(|time| reference_date.and_time(time))
+		})?;
+	match time_zone {
+		TimeZone::Utc => Some(Utc.from_utc_datetime(&naive)),
+		TimeZone::Local => Local
+			.from_local_datetime(&naive)
+			.earliest()
+			.map(|timestamp| timestamp.with_timezone(&Utc)),
+		TimeZone::Fixed(offset) => offset
+			.from_local_datetime(&naive)
+			.single()
+			.map(|timestamp| timestamp.with_timezone(&Utc)),
+	}
+}
+
+//Date of a timestamp as shown in the given time zone
+pub fn local_date(timestamp: &DateTime<Utc>, time_zone: &TimeZone) -> NaiveDate {
+	match time_zone {
+		TimeZone::Utc => timestamp.date_naive(),
+		TimeZone::Local => timestamp.with_timezone(&Local).date_naive(),
+		TimeZone::Fixed(offset) => timestamp.with_timezone(offset).date_naive(),
+	}
+}
+
+//Representative timestamp to measure the width of the timestamp column
+pub fn timestamp_sample(format: &str, time_zone: &TimeZone) -> String {
+	use chrono::TimeZone as _;
+	let timestamp = Utc
+		.with_ymd_and_hms(2000, 12, 28, 20, 58, 58) //wide digits and names
+		.unwrap();
+	format_timestamp(&timestamp, format, time_zone)
+}
+
+//Invalid specifiers make chrono fail while formatting, so check user input up front
+pub fn is_valid_timestamp_format(format: &str) -> bool {
+	!chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error)
+}
+
+pub const TIMESTAMP_PRECISIONS: [u32; 4] = [0, 3, 6, 9]; //digits of the fractional seconds
+
+pub const LOG_LEVELS: [log::LevelFilter; 6] = [
+	log::LevelFilter::Off,
+	log::LevelFilter::Error,
+	log::LevelFilter::Warn,
+	log::LevelFilter::Info,
+	log::LevelFilter::Debug,
+	log::LevelFilter::Trace,
+];
+
+pub struct Settings {
+	pub font: Option<String>, //Pango font description, None for the first preferred font
+	pub row_padding: f64,     //pixels above and below the text of each row
+	pub scroll_speed: i64,    //rows per scroll wheel step
+	pub timestamp_format: String, //chrono format without fractional seconds
+	pub timestamp_precision: u32, //see `TIMESTAMP_PRECISIONS`
+	pub time_zone: TimeZone,
+
+	//Severity visibility when opening a file without session
+	pub show_crit: bool,
+	pub show_err: bool,
+	pub show_warn: bool,
+	pub show_info: bool,
+	pub show_dbg: bool,
+	pub show_trace: bool,
+
+	pub theme: theme::ThemeKind,
+	pub severity_colors: [Option<theme::SeverityColors>; 6], //see `theme::severity_index`
+	pub log_level: log::LevelFilter,
+
+	pub copy_template: String,         //see `copy_format`
+	pub copy_timestamp_format: String, //chrono format, including fractional seconds
+	pub copy_line_ending: copy_format::LineEnding,
+
+	//Only edited in the file, see `correction`; None for `correction::default_rules`
+	pub correction_rules: Option<Vec<correction::CorrectionRule>>,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Settings {
+			font: None,
+			row_padding: 2.0,
+			scroll_speed: 3,
+			timestamp_format: "%d.%m.%y %T".to_string(),
+			timestamp_precision: 3,
+			time_zone: TimeZone::Utc,
+
+			show_crit: true,
+			show_err: true,
+			show_warn: true,
+			show_info: true,
+			show_dbg: true,
+			show_trace: true,
+
+			theme: theme::ThemeKind::System,
+			severity_colors: [None; 6],
+			log_level: log::LevelFilter::Trace,
+
+			copy_template: copy_format::DEFAULT_TEMPLATE.to_string(),
+			copy_timestamp_format: "%d-%m-%y %T%.6f".to_string(),
+			copy_line_ending: copy_format::LineEnding::Native,
+
+			correction_rules: None,
+		}
+	}
+}
+
+//Also holds the user's sfile layout, see `parse::sfile_layout`
+pub fn config_dir() -> std::path::PathBuf {
+	gtk::glib::user_config_dir().join("sherlog")
+}
+
+const SEVERITY_KEYS: [&str; 6] = ["critical", "error", "warning", "info", "debug", "trace"];
+
+impl Settings {
+	pub fn path() -> std::path::PathBuf {
+		config_dir().join("settings.toml")
+	}
+
+	//Missing file means defaults; malformed values fall back to defaults individually
+	pub fn load() -> Result<Settings, SettingsError> {
+		match std::fs::read_to_string(Settings::path()) {
+			Ok(contents) => Ok(Settings::from_toml(&contents.parse::<toml::Table>()?)),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	pub fn save(&self) -> Result<(), SettingsError> {
+		let path = Settings::path();
+		if let Some(dir) = path.parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		std::fs::write(path, self.to_toml().to_string())?;
+		Ok(())
+	}
+
+	//Chrono format of the timestamp column, including fractional seconds
+	pub fn full_timestamp_format(&self) -> String {
+		match self.timestamp_precision {
+			0 => self.timestamp_format.clone(),
+			precision => format!("{}%.{}f", self.timestamp_format, precision),
+		}
+	}
+
+	pub fn severity_visibility(&self) -> [bool; 6] {
+		[
+			self.show_crit,
+			self.show_err,
+			self.show_warn,
+			self.show_info,
+			self.show_dbg,
+			self.show_trace,
+		]
+	}
+
+	//Everything except the default severity visibility, which only applies when opening a file
+	pub fn apply(&self, store: &mut LogStoreLinear, default_font: &str) {
+		store.font = match &self.font {
+			Some(font) => gtk::pango::FontDescription::from_string(font),
+			None => crate::default_font(default_font),
+		};
+		store.row_padding = self.row_padding;
+		store.scroll_speed = self.scroll_speed;
+		store.timestamp_format = self.full_timestamp_format();
+		store.time_zone = self.time_zone;
+		store.theme_kind = self.theme;
+		store.severity_overrides = self.severity_colors;
+		store.update_theme();
+		store.copy_format = self.copy_format();
+	}
+
+	//`corrected_categories` of the sfile layout scope the default rules
+	pub fn correction_rules(
+		&self,
+		corrected_categories: &[&str],
+	) -> Vec<correction::CorrectionRule> {
+		match &self.correction_rules {
+			Some(rules) => rules.clone(),
+			None => correction::default_rules(corrected_categories),
+		}
+	}
+
+	pub fn copy_format(&self) -> copy_format::CopyFormat {
+		copy_format::CopyFormat::new(
+			&self.copy_template,
+			&self.copy_timestamp_format,
+			self.copy_line_ending,
+		)
+	}
+
+	fn to_toml(&self) -> toml::Table {
+		let mut table = toml::Table::new();
+		if let Some(font) = &self.font {
+			table.insert("font".to_string(), font.clone().into());
+		}
+		table.insert("row_padding".to_string(), self.row_padding.into());
+		table.insert("scroll_speed".to_string(), self.scroll_speed.into());
+		table.insert(
+			"timestamp_format".to_string(),
+			self.timestamp_format.clone().into(),
+		);
+		table.insert(
+			"timestamp_precision".to_string(),
+			i64::from(self.timestamp_precision).into(),
+		);
+		table.insert("time_zone".to_string(), self.time_zone.to_string().into());
+		table.insert("theme".to_string(), self.theme.id().into());
+		table.insert(
+			"log_level".to_string(),
+			self.log_level.as_str().to_lowercase().into(),
+		);
+
+		let mut copy = toml::Table::new();
+		copy.insert("template".to_string(), self.copy_template.clone().into());
+		copy.insert(
+			"timestamp_format".to_string(),
+			self.copy_timestamp_format.clone().into(),
+		);
+		copy.insert("line_ending".to_string(), self.copy_line_ending.id().into());
+		table.insert("copy".to_string(), toml::Value::Table(copy));
+
+		let mut severity = toml::Table::new();
+		for (key, show) in SEVERITY_KEYS.iter().zip(self.severity_visibility()) {
+			severity.insert(key.to_string(), show.into());
+		}
+		table.insert("severity".to_string(), toml::Value::Table(severity));
+
+		let mut severity_colors = toml::Table::new();
+		for (key, colors) in SEVERITY_KEYS.iter().zip(self.severity_colors.iter()) {
+			if let Some(colors) = colors {
+				let mut color_table = toml::Table::new();
+				color_table.insert(
+					"foreground".to_string(),
+					log_store::color_to_hex(colors.foreground).into(),
+				);
+				if let Some(background) = colors.background {
+					color_table.insert(
+						"background".to_string(),
+						log_store::color_to_hex(background).into(),
+					);
+				}
+				severity_colors.insert(key.to_string(), toml::Value::Table(color_table));
+			}
+		}
+		table.insert(
+			"severity_colors".to_string(),
+			toml::Value::Table(severity_colors),
+		);
+
+		if let Some(rules) = &self.correction_rules {
+			let rules = rules
+				.iter()
+				.map(|rule| toml::Value::Table(rule.to_toml()))
+				.collect();
+			table.insert(
+				"timestamp_correction".to_string(),
+				toml::Value::Array(rules),
+			);
+		}
+		table
+	}
+
+	fn from_toml(table: &toml::Table) -> Settings {
+		let default = Settings::default();
+		let severity = table.get("severity").and_then(|v| v.as_table());
+		let show = |name: &str| {
+			severity
+				.and_then(|severity| severity.get(name))
+				.and_then(|v| v.as_bool())
+				.unwrap_or(true)
+		};
+		let severity_colors = table.get("severity_colors").and_then(|v| v.as_table());
+		let colors = |name: &str| {
+			let color_table = severity_colors?.get(name)?.as_table()?;
+			Some(theme::SeverityColors {
+				foreground: log_store::color_from_hex(color_table.get("foreground")?.as_str()?)?,
+				background: color_table
+					.get("background")
+					.and_then(|v| v.as_str())
+					.and_then(log_store::color_from_hex),
+			})
+		};
+		let copy = table.get("copy").and_then(|v| v.as_table());
+		let copy_str = |name: &str| {
+			copy.and_then(|copy| copy.get(name))
+				.and_then(|v| v.as_str())
+		};
+		Settings {
+			font: table
+				.get("font")
+				.and_then(|v| v.as_str())
+				.map(|s| s.to_string()),
+			row_padding: table
+				.get("row_padding")
+				.and_then(|v| v.as_float())
+				.filter(|v| (0.0..=50.0).contains(v))
+				.unwrap_or(default.row_padding),
+			scroll_speed: table
+				.get("scroll_speed")
+				.and_then(|v| v.as_integer())
+				.filter(|v| (1..=100).contains(v))
+				.unwrap_or(default.scroll_speed),
+			timestamp_format: table
+				.get("timestamp_format")
+				.and_then(|v| v.as_str())
+				.filter(|v| is_valid_timestamp_format(v))
+				.map_or(default.timestamp_format, |s| s.to_string()),
+			timestamp_precision: table
+				.get("timestamp_precision")
+				.and_then(|v| v.as_integer())
+				.and_then(|v| TIMESTAMP_PRECISIONS.iter().find(|p| i64::from(**p) == v))
+				.copied()
+				.unwrap_or(default.timestamp_precision),
+			time_zone: table
+				.get("time_zone")
+				.and_then(|v| v.as_str())
+				.and_then(TimeZone::parse)
+				.unwrap_or(default.time_zone),
+
+			show_crit: show(SEVERITY_KEYS[0]),
+			show_err: show(SEVERITY_KEYS[1]),
+			show_warn: show(SEVERITY_KEYS[2]),
+			show_info: show(SEVERITY_KEYS[3]),
+			show_dbg: show(SEVERITY_KEYS[4]),
+			show_trace: show(SEVERITY_KEYS[5]),
+
+			theme: table
+				.get("theme")
+				.and_then(|v| v.as_str())
+				.and_then(theme::ThemeKind::from_id)
+				.unwrap_or(default.theme),
+			severity_colors: [
+				colors(SEVERITY_KEYS[0]),
+				colors(SEVERITY_KEYS[1]),
+				colors(SEVERITY_KEYS[2]),
+				colors(SEVERITY_KEYS[3]),
+				colors(SEVERITY_KEYS[4]),
+				colors(SEVERITY_KEYS[5]),
+			],
+			log_level: table
+				.get("log_level")
+				.and_then(|v| v.as_str())
+				.and_then(|v| v.parse::<log::LevelFilter>().ok())
+				.unwrap_or(default.log_level),
+
+			copy_template: copy_str("template").map_or(default.copy_template, |s| s.to_string()),
+			copy_timestamp_format: copy_str("timestamp_format")
+				.filter(|v| is_valid_timestamp_format(v))
+				.map_or(default.copy_timestamp_format, |s| s.to_string()),
+			copy_line_ending: copy_str("line_ending")
+				.and_then(copy_format::LineEnding::from_id)
+				.unwrap_or(default.copy_line_ending),
+
+			correction_rules: table
+				.get("timestamp_correction")
+				.and_then(|v| v.as_array())
+				.map(|rules| {
+					rules
+						.iter()
+						.filter_map(|rule| {
+							let parsed = rule
+								.as_table()
+								.and_then(correction::CorrectionRule::from_toml);
+							if parsed.is_none() {
+								log::warn!("Ignoring invalid timestamp correction rule: {}", rule);
+							}
+							parsed
+						})
+						.collect()
+				}),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_time_zone() {
+		assert_eq!(TimeZone::parse("UTC"), Some(TimeZone::Utc));
+		assert_eq!(TimeZone::parse(" local "), Some(TimeZone::Local));
+		assert_eq!(
+			TimeZone::parse("+05:30"),
+			chrono::FixedOffset::east_opt(19_800).map(TimeZone::Fixed)
+		);
+		assert_eq!(
+			TimeZone::parse("-3"),
+			chrono::FixedOffset::west_opt(10_800).map(TimeZone::Fixed)
+		);
+		assert_eq!(TimeZone::parse("+25:00"), None);
+		assert_eq!(TimeZone::parse("CET"), None);
+		let time_zone = TimeZone::parse("+02:00").unwrap();
+		assert_eq!(TimeZone::parse(&time_zone.to_string()), Some(time_zone));
+	}
+
+	#[test]
+	fn test_parse_timestamp() {
+		use chrono::TimeZone as _;
+		let date = NaiveDate::from_ymd_opt(2021, 3, 4).unwrap();
+		let expected = Utc.with_ymd_and_hms(2021, 3, 4, 14, 3, 0).unwrap();
+		let format = "%d.%m.%y %T%.3f";
+		assert_eq!(
+			parse_timestamp("04.03.21 14:03:00.000", format, &TimeZone::Utc, date),
+			Some(expected)
+		);
+		assert_eq!(
+			parse_timestamp("2021-03-04 14:03", format, &TimeZone::Utc, date),
+			Some(expected)
+		);
+		assert_eq!(
+			parse_timestamp(" 14:03 ", format, &TimeZone::Utc, date),
+			Some(expected)
+		);
+		let cet = TimeZone::parse("+01:00").unwrap();
+		assert_eq!(
+			parse_timestamp("15:03:00", format, &cet, date),
+			Some(expected)
+		);
+		assert_eq!(parse_timestamp("14h03", format, &TimeZone::Utc, date), None);
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let settings = Settings {
+			font: Some("DejaVu Sans Mono 12".to_string()),
+			row_padding: 4.0,
+			scroll_speed: 5,
+			timestamp_format: "%Y-%m-%d %T".to_string(),
+			timestamp_precision: 6,
+			time_zone: TimeZone::Local,
+			show_dbg: false,
+			show_trace: false,
+			theme: theme::ThemeKind::HighContrast,
+			severity_colors: [
+				None,
+				Some(theme::SeverityColors {
+					foreground: (1.0, 0.0, 0.0),
+					background: Some((0.0, 0.0, 1.0)),
+				}),
+				None,
+				None,
+				None,
+				None,
+			],
+			log_level: log::LevelFilter::Warn,
+			copy_template: "{severity}\t{field:PID}\t{message}".to_string(),
+			copy_line_ending: copy_format::LineEnding::CrLf,
+			correction_rules: Some(vec![correction::CorrectionRule {
+				name: "Uptime offset".to_string(),
+				pattern: regex::Regex::new(r"^Time offset (?P<delta>\d+)$").unwrap(),
+				unit: "ms".to_string(),
+				resolution: 1_000,
+				sources: regex::Regex::new("device").unwrap(),
+				session_field: "PID".to_string(),
+			}]),
+			..Default::default()
+		};
+		let text = settings.to_toml().to_string();
+		let restored = Settings::from_toml(&text.parse::<toml::Table>().unwrap());
+		assert_eq!(restored.font, settings.font);
+		assert_eq!(restored.row_padding, settings.row_padding);
+		assert_eq!(restored.scroll_speed, settings.scroll_speed);
+		assert_eq!(restored.full_timestamp_format(), "%Y-%m-%d %T%.6f");
+		assert_eq!(restored.time_zone, settings.time_zone);
+		assert_eq!(
+			restored.severity_visibility(),
+			[true, true, true, true, false, false]
+		);
+		assert_eq!(restored.theme, settings.theme);
+		assert_eq!(restored.severity_colors, settings.severity_colors);
+		assert_eq!(restored.copy_template, settings.copy_template);
+		assert_eq!(restored.copy_line_ending, settings.copy_line_ending);
+		assert_eq!(restored.log_level, settings.log_level);
+		let rules = restored.correction_rules(&[]);
+		assert_eq!(rules.len(), 1);
+		assert_eq!(rules[0].name, "Uptime offset");
+		assert_eq!(rules[0].pattern.as_str(), r"^Time offset (?P<delta>\d+)$");
+		assert_eq!(rules[0].unit, "ms");
+		assert_eq!(rules[0].resolution, 1_000);
+		assert_eq!(rules[0].sources.as_str(), "device");
+		assert_eq!(rules[0].session_field, "PID");
+		assert!(Settings::default()
+			.to_toml()
+			.get("timestamp_correction")
+			.is_none());
+	}
+}
@@ -0,0 +1,194 @@
+use crate::model;
+
+// Color themes of the custom-drawn log view. The light theme reproduces the
+// original hard-coded colors, the high-contrast theme uses the Okabe-Ito
+// palette, which stays distinguishable for color-blind users.
+
+pub type Color = (f64, f64, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeKind {
+	System, //Light or dark, following the GTK theme preference
+	Light,
+	Dark,
+	HighContrast,
+}
+
+impl ThemeKind {
+	pub const ALL: [ThemeKind; 4] = [
+		ThemeKind::System,
+		ThemeKind::Light,
+		ThemeKind::Dark,
+		ThemeKind::HighContrast,
+	];
+
+	//Stable identifier, used in GUI combo boxes and settings files
+	pub fn id(&self) -> &'static str {
+		match self {
+			ThemeKind::System => "system",
+			ThemeKind::Light => "light",
+			ThemeKind::Dark => "dark",
+			ThemeKind::HighContrast => "high-contrast",
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			ThemeKind::System => "Follow system",
+			ThemeKind::Light => "Light",
+			ThemeKind::Dark => "Dark",
+			ThemeKind::HighContrast => "High contrast",
+		}
+	}
+
+	pub fn from_id(id: &str) -> Option<ThemeKind> {
+		ThemeKind::ALL.iter().find(|kind| kind.id() == id).copied()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeverityColors {
+	pub foreground: Color,
+	pub background: Option<Color>, //None: Row background of the theme
+}
+
+impl SeverityColors {
+	//Color of bars and marks outside of text, e.g. in the scroll bar and timeline
+	pub fn mark(&self) -> Color {
+		self.background.unwrap_or(self.foreground)
+	}
+}
+
+pub struct Theme {
+	pub background: Color,
+	pub header_background: Color,
+	pub header_foreground: Color,
+	pub separator: Color,
+	pub hover: Color,
+	pub selected: Color,
+	pub selected_hover: Color, //Cumulative: Row is selected and hovered over
+	pub bookmark: Color,
+	pub indicator: Color, //e.g. "⏎ +N lines"
+	pub scroll_bar: Color,
+	pub scroll_thumb: Color,
+	pub search_hit: Color,             //marks in the scroll bar density overview
+	pub severity: [SeverityColors; 6], //see `severity_index`
+}
+
+const fn fg(foreground: Color) -> SeverityColors {
+	SeverityColors {
+		foreground,
+		background: None,
+	}
+}
+
+const fn fg_bg(foreground: Color, background: Color) -> SeverityColors {
+	SeverityColors {
+		foreground,
+		background: Some(background),
+	}
+}
+
+#[rustfmt::skip]
+impl Theme {
+	pub fn light() -> Theme {
+		Theme {
+			background:        (1.0, 1.0, 1.0),
+			header_background: (0.93, 0.93, 0.93),
+			header_foreground: (0.3, 0.3, 0.3),
+			separator:         (0.7, 0.7, 0.7),
+			hover:             (0.8, 0.8, 0.8),
+			selected:          (0.8, 0.8, 1.0),
+			selected_hover:    (0.7, 0.7, 1.0),
+			bookmark:          (0.2, 0.4, 1.0),
+			indicator:         (0.5, 0.5, 0.5),
+			scroll_bar:        (0.7, 0.7, 0.7),
+			scroll_thumb:      (0.3, 0.3, 0.3),
+			search_hit:        (0.0, 0.6, 0.3),
+			severity: [
+				fg((0.5, 0.0, 0.0)),   //Critical: Dark red
+				fg((1.0, 0.0, 0.0)),   //Error: Red
+				fg((0.77, 0.58, 0.0)), //Warning: Dirty yellow-orange
+				fg((0.0, 0.0, 0.0)),   //Info: Black
+				fg((0.6, 0.6, 0.6)),   //Debug: Grey
+				fg((0.4, 0.4, 0.4)),   //Trace: Light grey
+			],
+		}
+	}
+
+	pub fn dark() -> Theme {
+		Theme {
+			background:        (0.14, 0.14, 0.15),
+			header_background: (0.2, 0.2, 0.22),
+			header_foreground: (0.75, 0.75, 0.75),
+			separator:         (0.4, 0.4, 0.4),
+			hover:             (0.25, 0.25, 0.28),
+			selected:          (0.2, 0.25, 0.4),
+			selected_hover:    (0.25, 0.3, 0.5),
+			bookmark:          (0.35, 0.55, 1.0),
+			indicator:         (0.6, 0.6, 0.6),
+			scroll_bar:        (0.3, 0.3, 0.3),
+			scroll_thumb:      (0.6, 0.6, 0.6),
+			search_hit:        (0.3, 0.85, 0.5),
+			severity: [
+				fg_bg((1.0, 0.45, 0.45), (0.35, 0.0, 0.0)), //Critical
+				fg((1.0, 0.4, 0.4)),                        //Error
+				fg((0.95, 0.75, 0.2)),                      //Warning
+				fg((0.87, 0.87, 0.87)),                     //Info
+				fg((0.55, 0.55, 0.55)),                     //Debug
+				fg((0.45, 0.45, 0.45)),                     //Trace
+			],
+		}
+	}
+
+	pub fn high_contrast() -> Theme {
+		Theme {
+			background:        (1.0, 1.0, 1.0),
+			header_background: (0.0, 0.0, 0.0),
+			header_foreground: (1.0, 1.0, 1.0),
+			separator:         (0.0, 0.0, 0.0),
+			hover:             (0.85, 0.85, 0.85),
+			selected:          (0.6, 0.8, 1.0),
+			selected_hover:    (0.45, 0.7, 1.0),
+			bookmark:          (0.0, 0.447, 0.698),
+			indicator:         (0.0, 0.0, 0.0),
+			scroll_bar:        (0.8, 0.8, 0.8),
+			scroll_thumb:      (0.0, 0.0, 0.0),
+			search_hit:        (0.0, 0.62, 0.451), //Bluish green
+			severity: [
+				fg_bg((1.0, 1.0, 1.0), (0.835, 0.369, 0.0)),  //Critical: White on vermillion
+				fg((0.835, 0.369, 0.0)),                      //Error: Vermillion
+				fg_bg((0.0, 0.0, 0.0), (0.941, 0.894, 0.259)), //Warning: Black on yellow
+				fg((0.0, 0.0, 0.0)),                          //Info: Black
+				fg((0.0, 0.447, 0.698)),                      //Debug: Blue
+				fg((0.35, 0.35, 0.35)),                       //Trace: Grey
+			],
+		}
+	}
+}
+
+impl Theme {
+	pub fn new(kind: ThemeKind, system_dark: bool) -> Theme {
+		match kind {
+			ThemeKind::System if system_dark => Theme::dark(),
+			ThemeKind::System | ThemeKind::Light => Theme::light(),
+			ThemeKind::Dark => Theme::dark(),
+			ThemeKind::HighContrast => Theme::high_contrast(),
+		}
+	}
+
+	pub fn severity(&self, severity: &model::LogLevel) -> &SeverityColors {
+		&self.severity[severity_index(severity)]
+	}
+}
+
+pub fn severity_index(severity: &model::LogLevel) -> usize {
+	match severity {
+		model::LogLevel::Critical => 0,
+		model::LogLevel::Error => 1,
+		model::LogLevel::Warning => 2,
+		model::LogLevel::Info => 3,
+		model::LogLevel::Debug => 4,
+		model::LogLevel::Trace => 5,
+	}
+}
@@ -0,0 +1,174 @@
+use chrono::prelude::*;
+
+use crate::log_store::LogStoreLinear;
+use crate::model_internal;
+use crate::model_internal::{LogEntryExt, Timestamp};
+use crate::theme;
+
+// Histogram of log entries over time, shown in the timeline panel above the
+// log view. Bins have a "round" width (1 ms ... 30 days) chosen so that they
+// fit the panel. Entries hidden by the time range still count, so the
+// selected range can be changed from the timeline itself.
+
+const BIN_WIDTHS_MS: [i64; 30] = [
+	1,
+	2,
+	5,
+	10,
+	20,
+	50,
+	100,
+	200,
+	500,
+	1_000,
+	2_000,
+	5_000,
+	10_000,
+	15_000,
+	30_000,
+	60_000,
+	2 * 60_000,
+	5 * 60_000,
+	10 * 60_000,
+	15 * 60_000,
+	30 * 60_000,
+	3_600_000,
+	2 * 3_600_000,
+	3 * 3_600_000,
+	6 * 3_600_000,
+	12 * 3_600_000,
+	86_400_000,
+	2 * 86_400_000,
+	7 * 86_400_000,
+	30 * 86_400_000,
+];
+
+pub struct Histogram {
+	pub start: DateTime<Utc>, //start of the first bin, aligned to the bin width
+	pub bin_ms: i64,
+	pub bins: Vec<[u32; 6]>, //entry counts per severity, see `theme::severity_index`
+	pub max_bins: usize,     //bins that fit the panel when this histogram was built
+}
+
+impl Histogram {
+	pub fn end(&self) -> DateTime<Utc> {
+		self.start + chrono::Duration::milliseconds(self.bin_ms * self.bins.len() as i64)
+	}
+
+	//Position of a timestamp relative to the whole histogram, 0.0 to 1.0
+	pub fn fraction(&self, timestamp: &DateTime<Utc>) -> f64 {
+		let span_ms = (self.bin_ms * self.bins.len() as i64) as f64;
+		(*timestamp - self.start).num_milliseconds() as f64 / span_ms
+	}
+
+	pub fn timestamp(&self, fraction: f64) -> DateTime<Utc> {
+		let span_ms = (self.bin_ms * self.bins.len() as i64) as f64;
+		self.start + chrono::Duration::milliseconds((span_ms * fraction.clamp(0.0, 1.0)) as i64)
+	}
+
+	//chrono format of the axis labels, precise enough to tell bins apart
+	pub fn label_format(&self) -> &'static str {
+		if self.bin_ms < 1_000 {
+			"%T%.3f"
+		} else if self.bin_ms < 86_400_000 {
+			"%d.%m. %T"
+		} else {
+			"%d.%m.%y"
+		}
+	}
+}
+
+//Smallest round bin width so that `span_ms` takes up at most `max_bins` bins
+pub fn bin_width(span_ms: i64, max_bins: usize) -> i64 {
+	//One bin of slack, as the start is aligned down to the bin width
+	let max_bins = std::cmp::max(2, max_bins) as i64 - 1;
+	BIN_WIDTHS_MS
+		.iter()
+		.copied()
+		.find(|bin_ms| span_ms / bin_ms < max_bins)
+		.unwrap_or_else(|| span_ms / max_bins + 1)
+}
+
+//e.g. `500 ms`, `15 min`
+pub fn format_bin_width(bin_ms: i64) -> String {
+	for (unit_ms, unit) in [
+		(86_400_000, "d"),
+		(3_600_000, "h"),
+		(60_000, "min"),
+		(1_000, "s"),
+	] {
+		if bin_ms >= unit_ms && bin_ms % unit_ms == 0 {
+			return format!("{} {}", bin_ms / unit_ms, unit);
+		}
+	}
+	format!("{} ms", bin_ms)
+}
+
+//Bins all entries that are visible apart from the time range; None if there are none.
+//Boot-relative entries would stretch the range back to 1970, they are left out unless there are no others.
+pub fn histogram(store: &LogStoreLinear, max_bins: usize) -> Option<Histogram> {
+	let visible = |entry: &&LogEntryExt| entry.visible & !model_internal::VISIBLE_OFF_TIME == 0;
+	let only_boot_relative = !store
+		.store
+		.iter()
+		.filter(visible)
+		.any(|entry| !entry.timestamp.is_boot_relative());
+	let counted = |entry: &&LogEntryExt| {
+		visible(entry) && (only_boot_relative || !entry.timestamp.is_boot_relative())
+	};
+	let (min, max) = store.store.iter().filter(counted).fold(
+		None,
+		|range: Option<(Timestamp, Timestamp)>, entry| {
+			Some(match range {
+				Some((min, max)) => (min.min(entry.timestamp), max.max(entry.timestamp)),
+				None => (entry.timestamp, entry.timestamp),
+			})
+		},
+	)?;
+	let (min, max) = (min.to_datetime(), max.to_datetime());
+
+	let bin_ms = bin_width((max - min).num_milliseconds(), max_bins);
+	let start_ms = min.timestamp_millis().div_euclid(bin_ms) * bin_ms;
+	let start = Utc.timestamp_millis_opt(start_ms).single()?;
+	let bin_count = ((max.timestamp_millis() - start_ms) / bin_ms + 1) as usize;
+
+	let mut bins = vec![[0; 6]; bin_count];
+	for entry in store.store.iter().filter(counted) {
+		let bin = ((entry.datetime().timestamp_millis() - start_ms) / bin_ms) as usize;
+		bins[bin][theme::severity_index(&entry.severity)] += 1;
+	}
+	Some(Histogram {
+		start,
+		bin_ms,
+		bins,
+		max_bins,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_bin_width() {
+		assert_eq!(bin_width(0, 100), 1);
+		assert_eq!(bin_width(50, 100), 1);
+		assert_eq!(bin_width(250, 100), 5);
+		assert_eq!(bin_width(3_600_000, 100), 60_000);
+		assert_eq!(bin_width(7 * 86_400_000, 100), 2 * 3_600_000);
+		//Beyond the largest round width
+		assert_eq!(
+			bin_width(30 * 86_400_000 * 1_000, 10),
+			30 * 86_400_000 * 1_000 / 9 + 1
+		);
+	}
+
+	#[test]
+	fn test_format_bin_width() {
+		assert_eq!(format_bin_width(500), "500 ms");
+		assert_eq!(format_bin_width(15_000), "15 s");
+		assert_eq!(format_bin_width(2 * 60_000), "2 min");
+		assert_eq!(format_bin_width(7 * 86_400_000), "7 d");
+		assert_eq!(format_bin_width(1_500), "1500 ms");
+	}
+}